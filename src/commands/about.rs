@@ -17,24 +17,18 @@
  */
 
 use crate::commands::{Context, Error};
+use crate::i18n::{locale, tr};
 use serenity::builder::CreateEmbed;
 
-const TEXT: &str = r#"*Button Clicker* bot is a simple bot about gaining a score by pressing a button.
-There's not much to it currently but some features will come as a shop to exchange a "score" to a point multiplier, etc...
-Feel free to check up the dev's social if there's an issue with the bot!"#;
-
 /// Links to the bot's dev
 #[poise::command(slash_command)]
 pub async fn about(ctx: Context<'_>) -> Result<(), Error> {
+    let locale = locale(ctx).await?;
     let thumbnail = ctx.cache().current_user().avatar_url().unwrap_or_default();
     let embed = CreateEmbed::new()
-        .title("__About__")
-        .description(TEXT)
-        .field(
-            "__Discord__",
-            "alexou *(will not accept friend requests)*",
-            true,
-        )
+        .title(tr!(locale, "about.title"))
+        .description(tr!(locale, "about.text"))
+        .field("__Discord__", tr!(locale, "about.discord"), true)
         .field("__Twitter__", "[@Alekuso_](https://x.com/Alekuso_)", true)
         .field("__Github__", "[Alekuso](https://github.com/Alekuso)", false)
         .field(
@@ -42,20 +36,24 @@ pub async fn about(ctx: Context<'_>) -> Result<(), Error> {
             "[alex.tanukii.dev](https://bsky.app/profile/alex.tanukii.dev)",
             true,
         )
-        .field("__Website__", "[tanukii.dev](https://tanukii.dev)", true)
         .field(
-            "__Alex's community Server__",
+            tr!(locale, "about.website"),
+            "[tanukii.dev](https://tanukii.dev)",
+            true,
+        )
+        .field(
+            tr!(locale, "about.community"),
             "[Alex's Den](https://discord.gg/HWQXZJGCAM)",
             false,
         )
         .field(
-            "__Bot Invite__",
-            "**[Invite me !](https://discord.com/oauth2/authorize?client_id=774018602549772289)**",
+            tr!(locale, "about.invite"),
+            tr!(locale, "about.invite_link"),
             false,
         )
         .field(
-            "__Github Repository__",
-            "[Github Repository](https://github.com/Alekuso/Button-Clicker)",
+            tr!(locale, "about.repository"),
+            tr!(locale, "about.repository_link"),
             false,
         )
         .thumbnail(thumbnail)
@@ -0,0 +1,296 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::{Context, Error};
+use crate::config;
+use crate::error::BotError;
+use crate::i18n::{DEFAULT_LOCALE, locale, tr};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{Bson, DateTime, Document, doc};
+use mongodb::{Collection, Database};
+use serenity::all::{
+    ButtonStyle, ChannelId, CreateActionRow, CreateAttachment, CreateButton, CreateEmbed,
+    CreateInteractionResponse, CreateMessage,
+};
+use std::time::Duration;
+use tracing::{info, warn};
+
+// Collections holding documents that belong to a single user, keyed by user_id.
+// Any new collection storing user data has to be listed here (or handled below) for export and deletion.
+const USER_COLLECTIONS: [&str; 3] = ["transfers", "quests", "history"];
+
+// Replaces the ID of a deleted user in records that are kept for others
+pub const DELETED_USER: &str = "deleted";
+
+/// Export or delete the data Button Clicker stores about you
+#[poise::command(slash_command, subcommands("export", "delete"), subcommand_required)]
+pub async fn account(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Receive everything stored about you as a JSON file in your DMs
+#[poise::command(slash_command)]
+async fn export(ctx: Context<'_>) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let locale = locale(ctx).await?;
+    let user_id = ctx.author().id.to_string();
+
+    let users: Collection<Document> = db.collection("users");
+    let Some(user) = users.find_one(doc! { "user_id": &user_id }).await? else {
+        return Err(BotError::User(tr!(locale, "error.no_own_account")).into());
+    };
+
+    let mut data = doc! {
+        "exported_at": DateTime::now(),
+        "user": user,
+    };
+
+    for name in USER_COLLECTIONS {
+        data.insert(
+            name,
+            find_all(db, name, doc! { "user_id": &user_id }).await?,
+        );
+    }
+
+    data.insert(
+        "duels",
+        find_all(
+            db,
+            "duels",
+            doc! { "$or": [{ "challenger_id": &user_id }, { "opponent_id": &user_id }] },
+        )
+        .await?,
+    );
+    data.insert(
+        "lottery_tickets",
+        find_all(db, "lottery_tickets", doc! { "user_id": &user_id }).await?,
+    );
+    data.insert(
+        "lottery_wins",
+        find_all(db, "lottery_draws", doc! { "winner_id": &user_id }).await?,
+    );
+
+    // Relaxed extended JSON keeps dates and numbers readable
+    let json = serde_json::to_vec_pretty(&Bson::Document(data).into_relaxed_extjson())?;
+
+    let message = CreateMessage::new()
+        .content(tr!(locale, "account.export_dm"))
+        .add_file(CreateAttachment::bytes(json, "button_clicker_data.json"));
+
+    if ctx.author().direct_message(ctx, message).await.is_err() {
+        return Err(BotError::User(tr!(locale, "account.dm_closed")).into());
+    }
+
+    info!("Exported the data of {}", user_id);
+
+    let builder = poise::reply::CreateReply::default()
+        .content(tr!(locale, "account.export_sent"))
+        .ephemeral(true);
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Permanently delete your account and everything stored about you
+#[poise::command(slash_command)]
+async fn delete(ctx: Context<'_>) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let locale = locale(ctx).await?;
+    let author = ctx.author();
+
+    let users: Collection<Document> = db.collection("users");
+    if users
+        .find_one(doc! { "user_id": author.id.to_string() })
+        .await?
+        .is_none()
+    {
+        return Err(BotError::User(tr!(locale, "error.no_own_account")).into());
+    }
+
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "account.delete_title"))
+        .description(tr!(locale, "account.delete_confirm"))
+        .color(0x5754d0);
+
+    let builder = poise::reply::CreateReply::default()
+        .embed(embed)
+        .ephemeral(true)
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("account_delete")
+                .label(tr!(locale, "account.delete_button"))
+                .style(ButtonStyle::Danger),
+            CreateButton::new("account_cancel")
+                .label(tr!(locale, "give.cancel_button"))
+                .style(ButtonStyle::Secondary),
+        ])]);
+
+    let reply = ctx.send(builder).await?;
+    let msg = reply.message().await?;
+
+    let interaction = msg
+        .await_component_interaction(ctx)
+        .author_id(author.id)
+        .timeout(Duration::from_secs(60))
+        .await;
+
+    let confirmed = match interaction {
+        Some(interaction) => {
+            interaction
+                .create_response(ctx, CreateInteractionResponse::Acknowledge)
+                .await?;
+            interaction.data.custom_id == "account_delete"
+        }
+        None => false,
+    };
+
+    let description = if confirmed {
+        erase(db, &author.id.to_string()).await?;
+        info!("Deleted the account of {}", author.id);
+
+        if let Some(channel) = config::dynamic().log_channel_id {
+            let embed = CreateEmbed::new()
+                .title(tr!(DEFAULT_LOCALE, "account.deleted_log_title"))
+                .description(tr!(
+                    DEFAULT_LOCALE,
+                    "account.deleted_log",
+                    user_id = author.id
+                ))
+                .color(0x5754d0);
+            // The account is already gone, a failed log shouldn't report the deletion as failed
+            if let Err(e) = ChannelId::new(channel)
+                .send_message(ctx, CreateMessage::new().embed(embed))
+                .await
+            {
+                warn!("Failed to log the deletion of {}: {}", author.id, e);
+            }
+        }
+
+        tr!(locale, "account.deleted")
+    } else {
+        tr!(locale, "account.delete_cancelled")
+    };
+
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "account.delete_title"))
+        .description(description)
+        .color(0x5754d0);
+
+    reply
+        .edit(
+            ctx,
+            poise::reply::CreateReply::default()
+                .embed(embed)
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+async fn find_all(
+    db: &Database,
+    collection: &str,
+    filter: Document,
+) -> Result<Vec<Document>, Error> {
+    let collection: Collection<Document> = db.collection(collection);
+
+    Ok(collection.find(filter).await?.try_collect().await?)
+}
+
+/// Erases every record of a user. Records shared with other players keep existing for them,
+/// with the reference to the deleted user removed.
+async fn erase(db: &Database, user_id: &str) -> Result<(), Error> {
+    let mut session = db.client().start_session().await?;
+    session.start_transaction().await?;
+
+    db.collection::<Document>("users")
+        .delete_one(doc! { "user_id": user_id })
+        .session(&mut session)
+        .await?;
+
+    for name in USER_COLLECTIONS {
+        db.collection::<Document>(name)
+            .delete_many(doc! { "user_id": user_id })
+            .session(&mut session)
+            .await?;
+    }
+
+    // The other side of a transfer keeps its record, without knowing who it was
+    db.collection::<Document>("transfers")
+        .update_many(
+            doc! { "other_id": user_id },
+            doc! { "$unset": { "other_id": "" } },
+        )
+        .session(&mut session)
+        .await?;
+
+    // The opponent keeps the duel in their history, a duel the deleted user won still counts as won
+    for field in ["challenger_id", "opponent_id"] {
+        db.collection::<Document>("duels")
+            .update_many(doc! { field: user_id }, doc! { "$unset": { field: "" } })
+            .session(&mut session)
+            .await?;
+    }
+    db.collection::<Document>("duels")
+        .update_many(
+            doc! { "winner_id": user_id },
+            doc! { "$set": { "winner_id": DELETED_USER } },
+        )
+        .session(&mut session)
+        .await?;
+
+    // Tickets for draws that aren't drawn yet are withdrawn, a deleted user can't win the pot
+    let pending: Vec<Bson> = db
+        .collection::<Document>("lottery_draws")
+        .find(doc! { "status": { "$ne": "drawn" } })
+        .session(&mut session)
+        .await?
+        .stream(&mut session)
+        .try_collect::<Vec<Document>>()
+        .await?
+        .iter()
+        .filter_map(|draw| draw.get("_id").cloned())
+        .collect();
+    db.collection::<Document>("lottery_tickets")
+        .delete_many(doc! { "user_id": user_id, "draw_id": { "$in": pending } })
+        .session(&mut session)
+        .await?;
+
+    // Drawn tickets are kept in place, ticket ranges follow insertion order so the draws stay verifiable
+    db.collection::<Document>("lottery_tickets")
+        .update_many(
+            doc! { "user_id": user_id },
+            doc! { "$set": { "user_id": DELETED_USER } },
+        )
+        .session(&mut session)
+        .await?;
+
+    // Only the winner of a draw is forgotten
+    db.collection::<Document>("lottery_draws")
+        .update_many(
+            doc! { "winner_id": user_id },
+            doc! { "$set": { "winner_id": DELETED_USER } },
+        )
+        .session(&mut session)
+        .await?;
+
+    session.commit_transaction().await?;
+
+    Ok(())
+}
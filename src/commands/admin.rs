@@ -0,0 +1,73 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::{Context, Error};
+use crate::config;
+use crate::error::BotError;
+use crate::i18n::{locale, tr};
+
+/// Bot owner tools
+#[poise::command(
+    slash_command,
+    owners_only,
+    hide_in_help,
+    subcommands("reload"),
+    subcommand_required
+)]
+pub async fn admin(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Reload the config file without restarting the bot
+#[poise::command(slash_command, owners_only)]
+async fn reload(ctx: Context<'_>) -> Result<(), Error> {
+    let locale = locale(ctx).await?;
+
+    let reload = match config::reload(ctx).await {
+        Ok(reload) => reload,
+        Err(e) => {
+            return Err(BotError::User(tr!(locale, "admin.reload_failed", error = e)).into());
+        }
+    };
+
+    let mut content = if reload.applied.is_empty() {
+        tr!(locale, "admin.unchanged")
+    } else {
+        tr!(
+            locale,
+            "admin.reloaded",
+            applied = reload.applied.join(", ")
+        )
+    };
+    if !reload.restart_required.is_empty() {
+        content.push('\n');
+        content.push_str(&tr!(
+            locale,
+            "admin.restart_required",
+            fields = reload.restart_required.join(", ")
+        ));
+    }
+
+    let builder = poise::reply::CreateReply::default()
+        .content(content)
+        .ephemeral(true);
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
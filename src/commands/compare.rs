@@ -0,0 +1,166 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::history::score_at;
+use crate::commands::leaderboard::rank;
+use crate::commands::profile::find_by_id;
+use crate::commands::{Context, Error};
+use crate::error::BotError;
+use crate::format::number_format;
+use crate::i18n::{locale, tr};
+use mongodb::Collection;
+use mongodb::bson::{DateTime, Document};
+use serenity::all::{CreateEmbedFooter, User};
+use serenity::builder::CreateEmbed;
+
+// Time span used for the recent click rate
+const RECENT_HOURS: i64 = 24;
+
+/// Compare two players side by side
+#[poise::command(slash_command)]
+pub async fn compare(
+    ctx: Context<'_>,
+    #[description = "The first player"] first: User,
+    #[description = "(Optional) The second player, defaults to yourself"] second: Option<User>,
+) -> Result<(), Error> {
+    let second = second.unwrap_or_else(|| ctx.author().clone());
+
+    let embed = make_embed(ctx, &first, &second).await?;
+
+    ctx.send(poise::reply::CreateReply::default().embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+/// Compare your score with a member's
+#[poise::command(context_menu_command = "Compare scores")]
+pub async fn compare_scores(ctx: Context<'_>, user: User) -> Result<(), Error> {
+    let embed = make_embed(ctx, ctx.author(), &user).await?;
+
+    ctx.send(poise::reply::CreateReply::default().embed(embed))
+        .await?;
+
+    Ok(())
+}
+
+struct Stats {
+    name: String,
+    counter: i64,
+    place: u64,
+    // Score gained per hour over the last RECENT_HOURS, None if there's no snapshot old enough
+    recent_rate: Option<i64>,
+    duel_wins: i64,
+    duel_losses: i64,
+}
+
+async fn fetch_stats(ctx: Context<'_>, locale: &str, user: &User) -> Result<Stats, Error> {
+    let db = &ctx.data().db;
+    let collection: Collection<Document> = db.collection("users");
+    let user_id = user.id.to_string();
+
+    let Some(record) = find_by_id(&collection, &user_id).await? else {
+        return Err(BotError::User(tr!(locale, "error.no_account", name = user.name)).into());
+    };
+
+    let counter = record.get_i64("counter").unwrap_or(0);
+    let (place, _) = rank(&collection, counter).await?;
+
+    let since = DateTime::now().timestamp_millis() - RECENT_HOURS * 60 * 60 * 1000;
+    let recent_rate = score_at(db, &user_id, since)
+        .await?
+        .map(|before| (counter - before) / RECENT_HOURS);
+
+    Ok(Stats {
+        name: user.name.clone(),
+        counter,
+        place,
+        recent_rate,
+        duel_wins: record.get_i64("duel_wins").unwrap_or(0),
+        duel_losses: record.get_i64("duel_losses").unwrap_or(0),
+    })
+}
+
+async fn make_embed(ctx: Context<'_>, first: &User, second: &User) -> Result<CreateEmbed, Error> {
+    let locale = locale(ctx).await?;
+    let numbers = number_format(ctx, locale).await?;
+    let stats = [
+        fetch_stats(ctx, locale, first).await?,
+        fetch_stats(ctx, locale, second).await?,
+    ];
+
+    let verdict = match stats[0].counter.cmp(&stats[1].counter) {
+        std::cmp::Ordering::Equal => tr!(
+            locale,
+            "compare.tied",
+            first = stats[0].name,
+            second = stats[1].name
+        ),
+        ordering => {
+            let (ahead, behind) = if ordering.is_gt() {
+                (&stats[0], &stats[1])
+            } else {
+                (&stats[1], &stats[0])
+            };
+            tr!(
+                locale,
+                "compare.overtake",
+                behind = behind.name,
+                gap = numbers.score(ahead.counter - behind.counter + 1),
+                ahead = ahead.name
+            )
+        }
+    };
+
+    let mut embed = CreateEmbed::new()
+        .title(tr!(
+            locale,
+            "compare.title",
+            first = stats[0].name,
+            second = stats[1].name
+        ))
+        .description(verdict)
+        .color(0x5754d0)
+        .footer(CreateEmbedFooter::new(tr!(
+            locale,
+            "compare.footer",
+            hours = RECENT_HOURS
+        )));
+
+    for stats in &stats {
+        let rate = match stats.recent_rate {
+            Some(rate) => tr!(locale, "compare.rate", rate = numbers.number(rate)),
+            None => "-".to_string(),
+        };
+        embed = embed.field(
+            format!("__{}__", stats.name),
+            tr!(
+                locale,
+                "compare.stats",
+                score = numbers.score(stats.counter),
+                place = numbers.number(stats.place as i64),
+                rate = rate,
+                wins = numbers.number(stats.duel_wins),
+                losses = numbers.number(stats.duel_losses)
+            ),
+            true,
+        );
+    }
+
+    Ok(embed)
+}
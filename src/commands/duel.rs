@@ -0,0 +1,487 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::play::create_user;
+use crate::commands::{Context, Error};
+use crate::error::BotError;
+use crate::format::number_format;
+use crate::i18n::{locale, tr};
+use crate::shutdown::Session;
+use mongodb::bson::{DateTime, Document, doc};
+use mongodb::{Collection, Database};
+use serenity::all::{
+    ButtonStyle, CreateActionRow, CreateButton, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditMessage, Message, User,
+};
+use serenity::builder::CreateEmbed;
+use std::future::IntoFuture;
+use std::time::{Duration, Instant};
+use tracing::{error, info, warn};
+
+// How long the challenged user has to accept the duel
+const ACCEPT_TIMEOUT: Duration = Duration::from_secs(60);
+// How long the race itself lasts
+const RACE_DURATION: Duration = Duration::from_secs(15);
+
+/// Challenge another player to a click duel
+#[poise::command(slash_command, guild_only)]
+pub async fn duel(
+    ctx: Context<'_>,
+    #[description = "The player to challenge"] opponent: User,
+    #[description = "(Optional) The score both players put on the line"]
+    #[min = 1]
+    wager: Option<i64>,
+) -> Result<(), Error> {
+    let time = Instant::now();
+    let db = &ctx.data().db;
+    let collection: Collection<Document> = db.collection("users");
+    let author = ctx.author();
+    let wager = wager.unwrap_or(0);
+    // The whole duel is shown in the language of the challenger
+    let locale = locale(ctx).await?;
+    let numbers = number_format(ctx, locale).await?;
+
+    if opponent.id == author.id {
+        return Err(BotError::User(tr!(locale, "duel.self")).into());
+    }
+
+    if opponent.bot {
+        return Err(BotError::User(tr!(locale, "duel.bot")).into());
+    }
+
+    // Registered like /play sessions so a shutdown doesn't cut a race with escrowed wagers
+    let Some(mut session) = ctx.data().sessions.open() else {
+        return Err(BotError::User(tr!(locale, "duel.restarting")).into());
+    };
+
+    // Checks if the user has an account
+    // It creates a new account if the user doesn't have one
+    let challenger = match collection
+        .find_one(doc! {"user_id": author.id.to_string()})
+        .await?
+    {
+        Some(user) => user,
+        None => {
+            create_user(ctx, &collection).await?;
+            doc! {"counter": 0i64}
+        }
+    };
+
+    // The opponent can't be registered on their behalf since the account is tied to their own session.
+    let Some(challenged) = collection
+        .find_one(doc! {"user_id": opponent.id.to_string()})
+        .await?
+    else {
+        return Err(BotError::User(tr!(locale, "error.no_account", name = opponent.name)).into());
+    };
+
+    // Early check to avoid sending a challenge that can't be accepted anyway.
+    // The actual check happens when the wager is escrowed.
+    if wager > 0 {
+        if challenger.get_i64("counter").unwrap_or(0) < wager {
+            return Err(BotError::User(tr!(locale, "duel.not_enough")).into());
+        }
+        if challenged.get_i64("counter").unwrap_or(0) < wager {
+            return Err(BotError::User(tr!(
+                locale,
+                "duel.opponent_not_enough",
+                name = opponent.name
+            ))
+            .into());
+        }
+    }
+
+    info!("Duel challenge from {} to {}", author.id, opponent.id);
+
+    let wager_text = if wager > 0 {
+        tr!(locale, "duel.wager", wager = numbers.number(wager))
+    } else {
+        String::new()
+    };
+
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "duel.title"))
+        .description(tr!(
+            locale,
+            "duel.challenge",
+            challenger = author.name,
+            opponent = opponent.name,
+            wager = wager_text
+        ))
+        .color(0x5754d0)
+        .footer(CreateEmbedFooter::new(tr!(
+            locale,
+            "duel.accept_timeout",
+            name = opponent.name,
+            seconds = ACCEPT_TIMEOUT.as_secs()
+        )));
+
+    let builder = poise::reply::CreateReply::default()
+        .content(format!("<@{}>", opponent.id))
+        .embed(embed)
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("duel_accept")
+                .label(tr!(locale, "duel.accept"))
+                .style(ButtonStyle::Success),
+            CreateButton::new("duel_decline")
+                .label(tr!(locale, "duel.decline"))
+                .style(ButtonStyle::Danger),
+        ])]);
+
+    let mut msg = ctx.send(builder).await?.into_message().await?;
+
+    // Wait for the challenged user to answer
+    let accepted = loop {
+        let collector = msg
+            .await_component_interactions(ctx)
+            .timeout(ACCEPT_TIMEOUT);
+        let interaction = tokio::select! {
+            interaction = collector.into_future() => interaction,
+            () = session.closed() => {
+                end_for_shutdown(ctx, &mut msg, locale).await;
+                return Ok(());
+            }
+        };
+
+        match interaction {
+            Some(interaction) => {
+                interaction
+                    .create_response(ctx, CreateInteractionResponse::Acknowledge)
+                    .await?;
+
+                // Only the challenged user can answer
+                if interaction.user.id != opponent.id {
+                    continue;
+                }
+
+                break interaction.data.custom_id == "duel_accept";
+            }
+            None => break false,
+        }
+    };
+
+    if !accepted {
+        let embed = CreateEmbed::new()
+            .title(tr!(locale, "duel.title"))
+            .description(tr!(
+                locale,
+                "duel.not_accepted",
+                opponent = opponent.name,
+                challenger = author.name
+            ))
+            .color(0x5754d0);
+        msg.edit(ctx, EditMessage::new().embed(embed).components(vec![]))
+            .await?;
+        return Ok(());
+    }
+
+    let players = [author.id.to_string(), opponent.id.to_string()];
+
+    if wager > 0 && !escrow_wager(db, &players, wager).await? {
+        let embed = CreateEmbed::new()
+            .title(tr!(locale, "duel.title"))
+            .description(tr!(locale, "duel.cancelled"))
+            .color(0x5754d0);
+        msg.edit(ctx, EditMessage::new().embed(embed).components(vec![]))
+            .await?;
+        return Ok(());
+    }
+
+    // From here the wagers are escrowed, they must be given back if the race can't finish
+    let result = race(ctx, &mut msg, &mut session, locale, author, &opponent).await;
+    let clicks = match result {
+        Ok(Some(clicks)) => clicks,
+        result => {
+            refund(db, &players, wager).await;
+            // Interrupted by a shutdown unless the race failed
+            result?;
+            end_for_shutdown(ctx, &mut msg, locale).await;
+            return Ok(());
+        }
+    };
+
+    let winner = match clicks[0].cmp(&clicks[1]) {
+        std::cmp::Ordering::Greater => Some(0),
+        std::cmp::Ordering::Less => Some(1),
+        std::cmp::Ordering::Equal => None,
+    };
+
+    // Nothing was paid if the settle failed, the wagers are still escrowed
+    if let Err(e) = settle_duel(db, &players, winner, wager).await {
+        if winner.is_some() {
+            refund(db, &players, wager).await;
+        }
+        return Err(e);
+    }
+
+    db.collection::<Document>("duels")
+        .insert_one(doc! {
+            "challenger_id": &players[0],
+            "opponent_id": &players[1],
+            "challenger_clicks": clicks[0],
+            "opponent_clicks": clicks[1],
+            "winner_id": winner.map(|i| players[i].clone()),
+            "wager": wager,
+            "date": DateTime::now(),
+        })
+        .await?;
+
+    let names = [author.name.as_str(), opponent.name.as_str()];
+    let result = match winner {
+        Some(i) if wager > 0 => {
+            tr!(
+                locale,
+                "duel.win_wager",
+                name = names[i],
+                pot = numbers.score(wager * 2)
+            )
+        }
+        Some(i) => tr!(locale, "duel.win", name = names[i]),
+        None if wager > 0 => tr!(locale, "duel.draw_refunded"),
+        None => tr!(locale, "duel.draw"),
+    };
+
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "duel.title"))
+        .description(format!(
+            "{}: **{}**\n{}: **{}**\n\n{}",
+            names[0], clicks[0], names[1], clicks[1], result
+        ))
+        .color(0x5754d0);
+
+    msg.edit(ctx, EditMessage::new().embed(embed).components(vec![]))
+        .await?;
+
+    info!(
+        "Duel between {} and {} ended | Time: {:?}",
+        author.id,
+        opponent.id,
+        time.elapsed()
+    );
+
+    Ok(())
+}
+
+/// Runs the click race and returns the clicks of both players, None if the bot started shutting down.
+async fn race(
+    ctx: Context<'_>,
+    msg: &mut Message,
+    session: &mut Session,
+    locale: &str,
+    author: &User,
+    opponent: &User,
+) -> Result<Option<[i64; 2]>, Error> {
+    // Each player gets their own button, clicks on the other player's button are ignored
+    let buttons = vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("duel_{}", author.id))
+            .label(author.name.clone())
+            .style(ButtonStyle::Primary),
+        CreateButton::new(format!("duel_{}", opponent.id))
+            .label(opponent.name.clone())
+            .style(ButtonStyle::Secondary),
+    ])];
+
+    let mut clicks = [0i64; 2];
+    let deadline = Instant::now() + RACE_DURATION;
+
+    msg.edit(
+        ctx,
+        EditMessage::new()
+            .content("")
+            .embed(make_race_embed(
+                locale,
+                author,
+                opponent,
+                clicks,
+                RACE_DURATION,
+            ))
+            .components(buttons),
+    )
+    .await?;
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            break;
+        }
+
+        let collector = msg.await_component_interactions(ctx).timeout(remaining);
+        let interaction = tokio::select! {
+            interaction = collector.into_future() => interaction,
+            () = session.closed() => return Ok(None),
+        };
+
+        let Some(interaction) = interaction else {
+            break;
+        };
+
+        let player = if interaction.user.id == author.id {
+            0
+        } else if interaction.user.id == opponent.id {
+            1
+        } else {
+            interaction
+                .create_response(ctx, CreateInteractionResponse::Acknowledge)
+                .await?;
+            continue;
+        };
+
+        // A player can only click on their own button
+        if interaction.data.custom_id == format!("duel_{}", interaction.user.id) {
+            clicks[player] += 1;
+        }
+
+        // Answering with the update avoids a separate message edit, which is rate limited
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .embed(make_race_embed(locale, author, opponent, clicks, remaining)),
+                ),
+            )
+            .await?;
+    }
+
+    Ok(Some(clicks))
+}
+
+/// Tells the players the duel was called off by a shutdown, any wager is already refunded.
+async fn end_for_shutdown(ctx: Context<'_>, msg: &mut Message, locale: &str) {
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "duel.title"))
+        .description(tr!(locale, "duel.interrupted"))
+        .color(0x5754d0);
+
+    if let Err(e) = msg
+        .edit(ctx, EditMessage::new().embed(embed).components(vec![]))
+        .await
+    {
+        warn!("Failed to end the duel of {}: {}", ctx.author().id, e);
+    }
+}
+
+fn make_race_embed(
+    locale: &str,
+    author: &User,
+    opponent: &User,
+    clicks: [i64; 2],
+    remaining: Duration,
+) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(tr!(locale, "duel.title"))
+        .description(format!(
+            "{}: **{}**\n{}: **{}**",
+            author.name, clicks[0], opponent.name, clicks[1]
+        ))
+        .color(0x5754d0)
+        .footer(CreateEmbedFooter::new(tr!(
+            locale,
+            "duel.race_footer",
+            seconds = remaining.as_secs()
+        )))
+}
+
+/// Takes the wager from both players in a single transaction.
+/// Returns false if one of them can't afford it, in which case nothing is taken.
+async fn escrow_wager(db: &Database, players: &[String; 2], wager: i64) -> Result<bool, Error> {
+    let collection: Collection<Document> = db.collection("users");
+    let mut session = db.client().start_session().await?;
+    session.start_transaction().await?;
+
+    for player in players {
+        let result = collection
+            .update_one(
+                doc! {
+                    "user_id": player,
+                    "counter": { "$gte": wager }
+                },
+                doc! {
+                    "$inc": { "counter": -wager }
+                },
+            )
+            .session(&mut session)
+            .await?;
+
+        if result.modified_count == 0 {
+            session.abort_transaction().await?;
+            return Ok(false);
+        }
+    }
+
+    session.commit_transaction().await?;
+
+    Ok(true)
+}
+
+/// Pays out the escrowed wagers and records the duel stats of both players, in one transaction
+/// so a failure leaves the wagers escrowed and nothing paid.
+async fn settle_duel(
+    db: &Database,
+    players: &[String; 2],
+    winner: Option<usize>,
+    wager: i64,
+) -> Result<(), Error> {
+    let collection: Collection<Document> = db.collection("users");
+    let mut session = db.client().start_session().await?;
+    session.start_transaction().await?;
+
+    match winner {
+        Some(winner) => {
+            let loser = 1 - winner;
+            collection
+                .update_one(
+                    doc! { "user_id": &players[winner] },
+                    doc! { "$inc": { "counter": wager * 2, "duel_wins": 1i64 } },
+                )
+                .session(&mut session)
+                .await?;
+            collection
+                .update_one(
+                    doc! { "user_id": &players[loser] },
+                    doc! { "$inc": { "duel_losses": 1i64 } },
+                )
+                .session(&mut session)
+                .await?;
+        }
+        None if wager > 0 => {
+            collection
+                .update_many(
+                    doc! { "user_id": { "$in": players.to_vec() } },
+                    doc! { "$inc": { "counter": wager } },
+                )
+                .session(&mut session)
+                .await?;
+        }
+        None => {}
+    }
+
+    session.commit_transaction().await?;
+
+    Ok(())
+}
+
+/// Gives the escrowed wagers back when the duel can't be settled normally.
+async fn refund(db: &Database, players: &[String; 2], wager: i64) {
+    if wager > 0
+        && let Err(e) = settle_duel(db, players, None, wager).await
+    {
+        error!("Failed to refund the duel wagers of {:?}: {}", players, e);
+    }
+}
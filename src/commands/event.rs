@@ -0,0 +1,279 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::{Context, Error};
+use crate::error::BotError;
+use crate::i18n::{locale, tr};
+use futures::stream::TryStreamExt;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, Document, doc};
+use mongodb::{Collection, Database};
+use rand::Rng;
+use serenity::builder::CreateEmbed;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+// The active event is looked up on every click, so it's cached for a short time
+const CACHE_DURATION: Duration = Duration::from_secs(30);
+
+static CACHE: Mutex<Option<(Instant, Option<Event>)>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, poise::ChoiceParameter)]
+pub enum EventKind {
+    #[name = "Multiplier (every click is multiplied)"]
+    Multiplier,
+    #[name = "Lucky (some clicks are multiplied)"]
+    Lucky,
+}
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: ObjectId,
+    pub name: String,
+    pub kind: EventKind,
+    pub multiplier: i64,
+    // Only used by lucky events, chance for a click to be multiplied
+    pub chance: f64,
+    pub start: DateTime,
+    pub end: DateTime,
+}
+
+impl Event {
+    fn from_document(document: &Document) -> Option<Event> {
+        Some(Event {
+            id: document.get_object_id("_id").ok()?,
+            name: document.get_str("name").ok()?.to_string(),
+            kind: match document.get_str("kind").ok()? {
+                "lucky" => EventKind::Lucky,
+                _ => EventKind::Multiplier,
+            },
+            multiplier: document.get_i64("multiplier").ok()?,
+            chance: document.get_f64("chance").unwrap_or(1.0),
+            start: *document.get_datetime("start").ok()?,
+            end: *document.get_datetime("end").ok()?,
+        })
+    }
+
+    /// Points given for a single click while the event is running
+    pub fn roll_points(&self) -> i64 {
+        match self.kind {
+            EventKind::Multiplier => self.multiplier,
+            EventKind::Lucky if rand::thread_rng().gen_bool(self.chance.clamp(0.0, 1.0)) => {
+                self.multiplier
+            }
+            EventKind::Lucky => 1,
+        }
+    }
+
+    /// Short text used by the /play embed and the bot presence
+    pub fn summary(&self, locale: &str) -> String {
+        let kind = match self.kind {
+            EventKind::Multiplier => {
+                tr!(locale, "event.multiplier", multiplier = self.multiplier)
+            }
+            EventKind::Lucky => tr!(
+                locale,
+                "event.lucky",
+                chance = (self.chance * 100.0).round(),
+                multiplier = self.multiplier
+            ),
+        };
+        format!("{} ({})", self.name, kind)
+    }
+}
+
+/// Returns the running event with the best multiplier, if any.
+pub async fn active_event(db: &Database) -> Result<Option<Event>, Error> {
+    // An event that ended is never served from the cache, even if it's still fresh
+    if let Some((fetched, event)) = CACHE.lock().unwrap().as_ref()
+        && fetched.elapsed() < CACHE_DURATION
+        && event
+            .as_ref()
+            .is_none_or(|event| event.end > DateTime::now())
+    {
+        return Ok(event.clone());
+    }
+
+    let collection: Collection<Document> = db.collection("events");
+    let now = DateTime::now();
+    let event = collection
+        .find_one(doc! {
+            "start": { "$lte": now },
+            "end": { "$gt": now }
+        })
+        .sort(doc! { "multiplier": -1 })
+        .await?
+        .and_then(|event| Event::from_document(&event));
+
+    *CACHE.lock().unwrap() = Some((Instant::now(), event.clone()));
+
+    Ok(event)
+}
+
+fn clear_cache() {
+    *CACHE.lock().unwrap() = None;
+}
+
+/// Manage global bonus events (owner only)
+#[poise::command(
+    slash_command,
+    owners_only,
+    subcommands("schedule", "list", "cancel"),
+    subcommand_required
+)]
+pub async fn event(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Schedule a new bonus event
+#[poise::command(slash_command, owners_only)]
+async fn schedule(
+    ctx: Context<'_>,
+    #[description = "Name shown to the players"] name: String,
+    #[description = "How the multiplier is applied"] kind: EventKind,
+    #[description = "Points given per click"]
+    #[min = 2]
+    multiplier: i64,
+    #[description = "Duration of the event in minutes, up to 30 days"]
+    #[min = 1]
+    #[max = 43200]
+    duration: i64,
+    #[description = "(Optional) Minutes before the event starts, defaults to now, up to a year"]
+    #[min = 0]
+    #[max = 525600]
+    starts_in: Option<i64>,
+    #[description = "(Optional) Chance in percent for a click to be lucky, defaults to 10"]
+    #[min = 1]
+    #[max = 100]
+    chance: Option<i64>,
+) -> Result<(), Error> {
+    let collection: Collection<Document> = ctx.data().db.collection("events");
+    let locale = locale(ctx).await?;
+
+    let start =
+        DateTime::from_millis(DateTime::now().timestamp_millis() + starts_in.unwrap_or(0) * 60_000);
+    let end = DateTime::from_millis(start.timestamp_millis() + duration * 60_000);
+
+    collection
+        .insert_one(doc! {
+            "name": &name,
+            "kind": match kind {
+                EventKind::Multiplier => "multiplier",
+                EventKind::Lucky => "lucky",
+            },
+            "multiplier": multiplier,
+            "chance": chance.unwrap_or(10) as f64 / 100.0,
+            "start": start,
+            "end": end,
+        })
+        .await?;
+
+    clear_cache();
+    info!("Event {} scheduled by {}", name, ctx.author().id);
+
+    let builder = poise::reply::CreateReply::default()
+        .content(tr!(
+            locale,
+            "event.scheduled",
+            name = name,
+            start = start.timestamp_millis() / 1000,
+            end = end.timestamp_millis() / 1000
+        ))
+        .ephemeral(true);
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// List the running and upcoming bonus events
+#[poise::command(slash_command, owners_only)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let collection: Collection<Document> = ctx.data().db.collection("events");
+    let locale = locale(ctx).await?;
+
+    let events: Vec<Document> = collection
+        .find(doc! { "end": { "$gt": DateTime::now() } })
+        .sort(doc! { "start": 1 })
+        .limit(20)
+        .await?
+        .try_collect()
+        .await?;
+
+    let description = events
+        .iter()
+        .filter_map(Event::from_document)
+        .map(|event| {
+            format!(
+                "`{}` **{}**\n<t:{}:f> → <t:{}:f>",
+                event.id,
+                event.summary(locale),
+                event.start.timestamp_millis() / 1000,
+                event.end.timestamp_millis() / 1000
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "event.list_title"))
+        .description(if description.is_empty() {
+            tr!(locale, "event.none_scheduled")
+        } else {
+            description
+        })
+        .color(0x5754d0);
+
+    let builder = poise::reply::CreateReply::default()
+        .embed(embed)
+        .ephemeral(true);
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Cancel a bonus event
+#[poise::command(slash_command, owners_only)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "ID of the event, as shown by /event list"] id: String,
+) -> Result<(), Error> {
+    let collection: Collection<Document> = ctx.data().db.collection("events");
+    let locale = locale(ctx).await?;
+
+    let id =
+        ObjectId::parse_str(&id).map_err(|_| BotError::User(tr!(locale, "event.invalid_id")))?;
+    let result = collection.delete_one(doc! { "_id": id }).await?;
+
+    if result.deleted_count == 0 {
+        return Err(BotError::User(tr!(locale, "event.not_found")).into());
+    }
+
+    clear_cache();
+    info!("Event {} cancelled by {}", id, ctx.author().id);
+
+    let builder = poise::reply::CreateReply::default()
+        .content(tr!(locale, "event.cancelled"))
+        .ephemeral(true);
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
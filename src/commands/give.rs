@@ -0,0 +1,238 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::{Context, Error, utc_day};
+use crate::error::BotError;
+use crate::format::number_format;
+use crate::i18n::{locale, tr};
+use mongodb::bson::{DateTime, Document, doc};
+use mongodb::{Collection, Database};
+use serenity::all::{
+    ButtonStyle, CreateActionRow, CreateButton, CreateEmbedFooter, CreateInteractionResponse, User,
+};
+use serenity::builder::CreateEmbed;
+use std::time::Duration;
+use tracing::info;
+
+// Maximum amount of score a user can send per UTC day
+const DAILY_LIMIT: i64 = 1000;
+
+/// Give some of your score to another player
+#[poise::command(slash_command)]
+pub async fn give(
+    ctx: Context<'_>,
+    #[description = "The player receiving the score"] user: User,
+    #[description = "The amount of score to give"]
+    #[min = 1]
+    amount: i64,
+) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let collection: Collection<Document> = db.collection("users");
+    let author = ctx.author();
+    let locale = locale(ctx).await?;
+    let numbers = number_format(ctx, locale).await?;
+
+    if user.id == author.id {
+        return Err(BotError::User(tr!(locale, "give.self")).into());
+    }
+
+    let Some(sender) = collection
+        .find_one(doc! {"user_id": author.id.to_string()})
+        .await?
+    else {
+        return Err(BotError::User(tr!(locale, "error.no_own_account")).into());
+    };
+
+    if collection
+        .find_one(doc! {"user_id": user.id.to_string()})
+        .await?
+        .is_none()
+    {
+        return Err(BotError::User(tr!(locale, "error.no_account", name = user.name)).into());
+    }
+
+    // Early checks for a nicer error message, the transfer itself checks them again atomically.
+    if sender.get_i64("counter").unwrap_or(0) < amount {
+        return Err(BotError::User(tr!(locale, "give.not_enough")).into());
+    }
+
+    let sent_today = sent_today(&sender);
+    if sent_today + amount > DAILY_LIMIT {
+        return Err(BotError::User(tr!(
+            locale,
+            "give.limit",
+            left = numbers.number(DAILY_LIMIT - sent_today)
+        ))
+        .into());
+    }
+
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "give.title"))
+        .description(tr!(
+            locale,
+            "give.confirm",
+            amount = numbers.number(amount),
+            name = user.name
+        ))
+        .color(0x5754d0)
+        .footer(CreateEmbedFooter::new(tr!(
+            locale,
+            "give.left_today",
+            left = numbers.number(DAILY_LIMIT - sent_today)
+        )));
+
+    let builder = poise::reply::CreateReply::default()
+        .embed(embed)
+        .ephemeral(true)
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("give_confirm")
+                .label(tr!(locale, "give.confirm_button"))
+                .style(ButtonStyle::Success),
+            CreateButton::new("give_cancel")
+                .label(tr!(locale, "give.cancel_button"))
+                .style(ButtonStyle::Secondary),
+        ])]);
+
+    let reply = ctx.send(builder).await?;
+    let msg = reply.message().await?;
+
+    let interaction = msg
+        .await_component_interaction(ctx)
+        .author_id(author.id)
+        .timeout(Duration::from_secs(60))
+        .await;
+
+    let description = match interaction {
+        Some(interaction) => {
+            interaction
+                .create_response(ctx, CreateInteractionResponse::Acknowledge)
+                .await?;
+
+            if interaction.data.custom_id != "give_confirm" {
+                tr!(locale, "give.cancelled")
+            } else if transfer(db, &author.id.to_string(), &user.id.to_string(), amount).await? {
+                info!("Transfer of {} from {} to {}", amount, author.id, user.id);
+                tr!(
+                    locale,
+                    "give.done",
+                    amount = numbers.number(amount),
+                    name = user.name
+                )
+            } else {
+                tr!(locale, "give.failed")
+            }
+        }
+        None => tr!(locale, "give.cancelled"),
+    };
+
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "give.title"))
+        .description(description)
+        .color(0x5754d0);
+
+    reply
+        .edit(
+            ctx,
+            poise::reply::CreateReply::default()
+                .embed(embed)
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Amount of score the user already sent during the current UTC day
+fn sent_today(user: &Document) -> i64 {
+    if user.get_i64("transfer_day").ok() == Some(utc_day(DateTime::now())) {
+        user.get_i64("transferred_today").unwrap_or(0)
+    } else {
+        0
+    }
+}
+
+/// Moves `amount` from the sender to the receiver in a single transaction.
+/// Returns false without changing anything if the sender can't afford it or exceeds the daily limit.
+async fn transfer(db: &Database, from: &str, to: &str, amount: i64) -> Result<bool, Error> {
+    let users: Collection<Document> = db.collection("users");
+    let transfers: Collection<Document> = db.collection("transfers");
+    let now = DateTime::now();
+    let today = utc_day(now);
+
+    let mut session = db.client().start_session().await?;
+    session.start_transaction().await?;
+
+    // The balance and the daily limit are checked in the filter, so two concurrent transfers can't both pass.
+    let result = users
+        .update_one(
+            doc! {
+                "user_id": from,
+                "counter": { "$gte": amount },
+                "$or": [
+                    { "transfer_day": { "$ne": today } },
+                    { "transferred_today": { "$lte": DAILY_LIMIT - amount } }
+                ]
+            },
+            vec![doc! {
+                "$set": {
+                    "counter": { "$subtract": ["$counter", amount] },
+                    "transferred_today": {
+                        "$cond": [
+                            { "$eq": ["$transfer_day", today] },
+                            { "$add": ["$transferred_today", amount] },
+                            amount
+                        ]
+                    },
+                    "transfer_day": today
+                }
+            }],
+        )
+        .session(&mut session)
+        .await?;
+
+    if result.modified_count == 0 {
+        session.abort_transaction().await?;
+        return Ok(false);
+    }
+
+    let result = users
+        .update_one(
+            doc! { "user_id": to },
+            doc! { "$inc": { "counter": amount } },
+        )
+        .session(&mut session)
+        .await?;
+
+    if result.modified_count == 0 {
+        session.abort_transaction().await?;
+        return Ok(false);
+    }
+
+    // Each party gets their own history entry, the amount is negative for the sender
+    transfers
+        .insert_many(vec![
+            doc! { "user_id": from, "other_id": to, "amount": -amount, "date": now },
+            doc! { "user_id": to, "other_id": from, "amount": amount, "date": now },
+        ])
+        .session(&mut session)
+        .await?;
+
+    session.commit_transaction().await?;
+
+    Ok(true)
+}
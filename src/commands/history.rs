@@ -0,0 +1,329 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::{Context, Error};
+use crate::error::BotError;
+use crate::format::number_format;
+use crate::i18n::{locale, tr};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, Document, doc};
+use mongodb::{Collection, Database};
+use serenity::all::{CreateAttachment, CreateEmbedFooter, User};
+use serenity::builder::CreateEmbed;
+use std::time::{Duration, Instant};
+use tiny_skia::{Color, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
+use tracing::{info, warn};
+
+// How often a snapshot of every changed score is taken
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(3600);
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 400;
+const PADDING: f32 = 24.0;
+
+// Colors of the first and second line, the first one is the usual embed color
+const COLORS: [(u8, u8, u8); 2] = [(0x57, 0x54, 0xd0), (0xf0, 0xa0, 0x30)];
+
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum Range {
+    #[name = "Last 24 hours"]
+    Day,
+    #[name = "Last 7 days"]
+    Week,
+    #[name = "Last 30 days"]
+    Month,
+    #[name = "Last year"]
+    Year,
+}
+
+impl Range {
+    fn key(self) -> &'static str {
+        match self {
+            Range::Day => "history.range_day",
+            Range::Week => "history.range_week",
+            Range::Month => "history.range_month",
+            Range::Year => "history.range_year",
+        }
+    }
+
+    fn millis(self) -> i64 {
+        let day = 24 * 60 * 60 * 1000;
+        match self {
+            Range::Day => day,
+            Range::Week => 7 * day,
+            Range::Month => 30 * day,
+            Range::Year => 365 * day,
+        }
+    }
+}
+
+/// View how your score evolved over time
+#[poise::command(slash_command)]
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "(Optional) Time range of the chart, defaults to the last 7 days"]
+    range: Option<Range>,
+    #[description = "(Optional) A user to compare with"] compare: Option<User>,
+) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let locale = locale(ctx).await?;
+    let numbers = number_format(ctx, locale).await?;
+    let range = range.unwrap_or(Range::Week);
+    let now = DateTime::now().timestamp_millis();
+    let start = now - range.millis();
+
+    let mut users = vec![ctx.author().clone()];
+    users.extend(compare);
+
+    let mut series = Vec::new();
+    for user in &users {
+        match fetch_series(db, &user.id.to_string(), start, now).await? {
+            Some(points) => series.push(points),
+            None => {
+                return Err(
+                    BotError::User(tr!(locale, "error.no_account", name = user.name)).into(),
+                );
+            }
+        }
+    }
+
+    let png = render_chart(&series, start, now)?;
+
+    let mut description = String::new();
+    for ((user, points), color) in users.iter().zip(&series).zip(["🟣", "🟠"]) {
+        let first = points.first().map_or(0, |p| p.1);
+        let last = points.last().map_or(0, |p| p.1);
+        // Negative scores already come with their sign
+        let sign = if last >= first { "+" } else { "" };
+        description.push_str(&format!(
+            "{} **{}**: {} → {} ({}{})\n",
+            color,
+            user.name,
+            numbers.score(first),
+            numbers.score(last),
+            sign,
+            numbers.score(last - first)
+        ));
+    }
+
+    let (min, max) = bounds(&series);
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "history.title"))
+        .description(description)
+        .image("attachment://history.png")
+        .color(0x5754d0)
+        .footer(CreateEmbedFooter::new(tr!(
+            locale,
+            "history.footer",
+            range = tr!(locale, range.key()),
+            min = numbers.score(min),
+            max = numbers.score(max)
+        )));
+
+    let builder = poise::reply::CreateReply::default()
+        .embed(embed)
+        .attachment(CreateAttachment::bytes(png, "history.png"));
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Points (time in millis, score) of a user between `start` and `end`, ending with the current score.
+/// Returns None if the user isn't registered.
+async fn fetch_series(
+    db: &Database,
+    user_id: &str,
+    start: i64,
+    end: i64,
+) -> Result<Option<Vec<(i64, i64)>>, Error> {
+    let users: Collection<Document> = db.collection("users");
+    let history: Collection<Document> = db.collection("history");
+
+    let Some(user) = users.find_one(doc! { "user_id": user_id }).await? else {
+        return Ok(None);
+    };
+
+    let mut points = Vec::new();
+
+    // The score at the start of the range is the last one known before it
+    if let Some(before) = score_at(db, user_id, start).await? {
+        points.push((start, before));
+    }
+
+    let snapshots: Vec<Document> = history
+        .find(doc! { "user_id": user_id, "at": { "$gte": DateTime::from_millis(start) } })
+        .sort(doc! { "at": 1 })
+        .await?
+        .try_collect()
+        .await?;
+
+    for snapshot in snapshots {
+        if let (Ok(at), Ok(counter)) = (snapshot.get_datetime("at"), snapshot.get_i64("counter")) {
+            points.push((at.timestamp_millis(), counter));
+        }
+    }
+
+    points.push((end, user.get_i64("counter").unwrap_or(0)));
+
+    Ok(Some(points))
+}
+
+/// Score of the last snapshot taken before `at` (in millis), if any.
+pub async fn score_at(db: &Database, user_id: &str, at: i64) -> Result<Option<i64>, Error> {
+    let history: Collection<Document> = db.collection("history");
+
+    let snapshot = history
+        .find_one(doc! { "user_id": user_id, "at": { "$lt": DateTime::from_millis(at) } })
+        .sort(doc! { "at": -1 })
+        .await?;
+
+    Ok(snapshot.and_then(|snapshot| snapshot.get_i64("counter").ok()))
+}
+
+fn bounds(series: &[Vec<(i64, i64)>]) -> (i64, i64) {
+    let scores = series.iter().flatten().map(|p| p.1);
+    let min = scores.clone().min().unwrap_or(0);
+    let max = scores.max().unwrap_or(0);
+    (min, max)
+}
+
+/// Draws the line chart of every series and encodes it as a PNG.
+fn render_chart(series: &[Vec<(i64, i64)>], start: i64, end: i64) -> Result<Vec<u8>, Error> {
+    let mut pixmap = Pixmap::new(WIDTH, HEIGHT).ok_or("Failed to allocate the chart")?;
+    pixmap.fill(Color::from_rgba8(0x2b, 0x2d, 0x31, 0xff));
+
+    let left = PADDING;
+    let top = PADDING;
+    let width = WIDTH as f32 - 2.0 * PADDING;
+    let height = HEIGHT as f32 - 2.0 * PADDING;
+
+    // Horizontal grid lines
+    let mut grid = Paint::default();
+    grid.set_color_rgba8(0x40, 0x42, 0x49, 0xff);
+    for i in 0..=4 {
+        let y = top + height * i as f32 / 4.0;
+        if let Some(rect) = Rect::from_xywh(left, y, width, 1.0) {
+            pixmap.fill_rect(rect, &grid, Transform::identity(), None);
+        }
+    }
+
+    let (min, max) = bounds(series);
+    // Avoids a division by zero when the score never changed, the line is then drawn in the middle
+    let span = (max - min).max(1) as f32;
+    let offset = if max == min { height / 2.0 } else { 0.0 };
+
+    for (points, (r, g, b)) in series.iter().zip(COLORS) {
+        let mut path = PathBuilder::new();
+        for (i, (at, counter)) in points.iter().enumerate() {
+            let x = left + width * (at - start) as f32 / (end - start).max(1) as f32;
+            let y = top + height - height * (counter - min) as f32 / span - offset;
+            if i == 0 {
+                // A single point can't make a line, it's drawn flat from the start of the range instead
+                path.move_to(if points.len() == 1 { left } else { x }, y);
+            }
+            path.line_to(x, y);
+        }
+
+        let Some(path) = path.finish() else {
+            continue;
+        };
+
+        let mut paint = Paint::default();
+        paint.set_color_rgba8(r, g, b, 0xff);
+        paint.anti_alias = true;
+
+        let stroke = Stroke {
+            width: 3.0,
+            ..Default::default()
+        };
+
+        pixmap.stroke_path(&path, &paint, &stroke, Transform::identity(), None);
+    }
+
+    Ok(pixmap.encode_png()?)
+}
+
+/// Background task saving a snapshot of every score that changed since the last one.
+pub async fn run_snapshots(db: Database) {
+    loop {
+        if let Err(e) = take_snapshots(&db).await {
+            warn!("Failed to take score snapshots: {}", e);
+        }
+
+        tokio::time::sleep(SNAPSHOT_INTERVAL).await;
+    }
+}
+
+async fn take_snapshots(db: &Database) -> Result<(), Error> {
+    let time = Instant::now();
+    let users: Collection<Document> = db.collection("users");
+    let history: Collection<Document> = db.collection("history");
+    let now = DateTime::now();
+
+    // snapshot_counter is the score of the last snapshot, unchanged scores are skipped
+    let changed: Vec<Document> = users
+        .find(doc! { "$expr": { "$ne": ["$counter", "$snapshot_counter"] } })
+        .projection(doc! { "user_id": 1, "counter": 1 })
+        .await?
+        .try_collect()
+        .await?;
+
+    if changed.is_empty() {
+        return Ok(());
+    }
+
+    let snapshots: Vec<Document> = changed
+        .iter()
+        .filter_map(|user| {
+            Some(doc! {
+                "user_id": user.get_str("user_id").ok()?,
+                "counter": user.get_i64("counter").ok()?,
+                "at": now
+            })
+        })
+        .collect();
+
+    history.insert_many(&snapshots).await?;
+
+    // Only the users still at the snapshotted score are marked, in a single update.
+    // A score that changed meanwhile keeps its old snapshot_counter and is snapshotted on the next run.
+    let snapshotted: Vec<Document> = snapshots
+        .iter()
+        .map(|snapshot| {
+            doc! {
+                "user_id": snapshot.get("user_id"),
+                "counter": snapshot.get("counter")
+            }
+        })
+        .collect();
+    users
+        .update_many(
+            doc! { "$or": snapshotted },
+            vec![doc! { "$set": { "snapshot_counter": "$counter" } }],
+        )
+        .await?;
+
+    info!(
+        "Took {} score snapshots | Time: {:?}",
+        snapshots.len(),
+        time.elapsed()
+    );
+
+    Ok(())
+}
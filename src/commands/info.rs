@@ -17,8 +17,10 @@
  */
 
 use crate::commands::{Context, Error};
+use crate::format::number_format;
+use crate::i18n::{locale, tr};
 use mongodb::Collection;
-use mongodb::bson::{Document, doc};
+use mongodb::bson::{Bson, Document, doc};
 use readable::byte::*;
 use readable::up::*;
 use serenity::all::CreateEmbedFooter;
@@ -29,11 +31,19 @@ use sysinfo::System;
 #[poise::command(slash_command)]
 pub async fn info(ctx: Context<'_>) -> Result<(), Error> {
     let db = &ctx.data().db;
+    let locale = locale(ctx).await?;
+    let numbers = number_format(ctx, locale).await?;
 
     let db_stats = db.run_command(doc! {"dbStats": 1}).await?;
-    let storage_size = db_stats.get_i64("storageSize").unwrap(); // in bytes
+    // in bytes, depending on the server version this is either an integer or a double
+    let storage_size = match db_stats.get("storageSize") {
+        Some(Bson::Int64(size)) => Some(*size as u64),
+        Some(Bson::Int32(size)) => Some(*size as u64),
+        Some(Bson::Double(size)) => Some(*size as u64),
+        _ => None,
+    };
 
-    let readable_storage_size = Byte::from(storage_size);
+    let readable_storage_size = readable_bytes(storage_size);
 
     let uptime = UptimeFull::from(ctx.data().uptime);
 
@@ -42,17 +52,23 @@ pub async fn info(ctx: Context<'_>) -> Result<(), Error> {
         .collection::<Collection<Document>>("users")
         .count_documents(doc! {})
         .await?;
-    let total_sessions = db
-        .collection::<Collection<Document>>("session")
-        .count_documents(doc! {})
-        .await?;
-    let memory_usage = Byte::from(get_memory_usage());
+    let total_sessions = ctx.data().sessions.count();
+    let memory_usage = readable_bytes(get_memory_usage());
 
     let thumbnail = ctx.cache().current_user().avatar_url().unwrap_or_default();
-    let footer = CreateEmbedFooter::new("Made with ❤️ by Alex");
+    let footer = CreateEmbedFooter::new(tr!(locale, "info.footer"));
     let embed = CreateEmbed::new()
-        .title("__Bot Info__")
-        .description(format!("**📡 Server count**: {}\n**🔌 Registered users**: {}\n**💡 Current active sessions**: {}\n**🗃️ RAM Usage**: {}\n**📝 DB Storage Usage**: {}\n**🕐 Uptime**: {}", server_count, total_users, total_sessions, memory_usage, readable_storage_size, uptime))
+        .title(tr!(locale, "info.title"))
+        .description(tr!(
+            locale,
+            "info.description",
+            servers = numbers.number(server_count as i64),
+            users = numbers.number(total_users as i64),
+            sessions = numbers.number(total_sessions as i64),
+            memory = memory_usage,
+            storage = readable_storage_size,
+            uptime = uptime
+        ))
         .thumbnail(thumbnail)
         .color(0x5754d0)
         .footer(footer);
@@ -64,14 +80,19 @@ pub async fn info(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-fn get_memory_usage() -> u64 {
+// Both stats are only informative, show a placeholder instead of failing the whole command
+fn readable_bytes(bytes: Option<u64>) -> String {
+    bytes.map_or_else(|| String::from("-"), |bytes| Byte::from(bytes).to_string())
+}
+
+fn get_memory_usage() -> Option<u64> {
     let mut system = System::new_all();
 
     system.refresh_memory();
 
     let pid = std::process::id() as usize;
 
-    let process = system.process(pid.into()).unwrap();
+    let process = system.process(pid.into())?;
 
-    process.memory()
+    Some(process.memory())
 }
@@ -16,7 +16,10 @@
  *  as defined by the AGPLv3 license.
  */
 
-use crate::commands::{Context, Error};
+use crate::commands::settings::{Settings, Visibility};
+use crate::commands::{Context, Error, display_with_handle};
+use crate::format::NumberFormat;
+use crate::i18n::{locale_for, tr};
 use futures::stream::TryStreamExt;
 use mongodb::Collection;
 use mongodb::bson::{Document, doc};
@@ -35,10 +38,13 @@ enum Filter {
 pub async fn leaderboard(ctx: Context<'_>) -> Result<(), Error> {
     let db = &ctx.data().db;
     let collection: Collection<Document> = db.collection("users");
+    let settings = Settings::load(ctx).await?;
+    let locale = locale_for(ctx, &settings);
+    let numbers = NumberFormat::new(locale, settings.short_numbers);
 
     let users = get_users(&collection, Filter::ScoreDesc).await?;
 
-    let embed = make_embed(ctx, users, Filter::ScoreDesc).await;
+    let embed = make_embed(ctx, locale, numbers, users, Filter::ScoreDesc).await;
 
     let builder = poise::reply::CreateReply::default().embed(embed);
     // .components(vec![
@@ -52,15 +58,13 @@ pub async fn leaderboard(ctx: Context<'_>) -> Result<(), Error> {
     let msg = ctx.send(builder).await?.into_message().await?;
 
     loop {
-        let interaction = msg
+        let Some(interaction) = msg
             .await_component_interactions(ctx)
             .timeout(Duration::from_secs(600))
-            .await;
-
-        if interaction.is_none() {
+            .await
+        else {
             break;
-        }
-        let interaction = interaction.unwrap();
+        };
 
         let interaction_time = std::time::Instant::now();
 
@@ -68,11 +72,11 @@ pub async fn leaderboard(ctx: Context<'_>) -> Result<(), Error> {
         let embed = match interaction.data.custom_id.as_str() {
             "asc" => {
                 let users = get_users(&collection, Filter::ScoreAsc).await?;
-                make_embed(ctx, users, Filter::ScoreAsc).await
+                make_embed(ctx, locale, numbers, users, Filter::ScoreAsc).await
             }
             "desc" => {
                 let users = get_users(&collection, Filter::ScoreDesc).await?;
-                make_embed(ctx, users, Filter::ScoreDesc).await
+                make_embed(ctx, locale, numbers, users, Filter::ScoreDesc).await
             }
             _ => {
                 continue;
@@ -102,7 +106,7 @@ async fn get_users(
     let cursor = match filter {
         Filter::ScoreAsc => {
             collection
-                .find(doc! {})
+                .find(visible())
                 .sort(doc! { "counter": 1 })
                 .skip(0)
                 .limit(10)
@@ -110,7 +114,7 @@ async fn get_users(
         }
         Filter::ScoreDesc => {
             collection
-                .find(doc! {})
+                .find(visible())
                 .sort(doc! { "counter": -1 })
                 .skip(0)
                 .limit(10)
@@ -123,31 +127,61 @@ async fn get_users(
     Ok(users)
 }
 
-async fn author_place(ctx: &Context<'_>) -> Result<(i64, usize), Error> {
+// Hidden users aren't listed, anonymous ones are listed without their name and avatar
+fn visible() -> Document {
+    doc! { "settings.leaderboard": { "$ne": Visibility::Hidden.as_str() } }
+}
+
+/// Filter matching the users who can be looked up by name, the ones who didn't opt out of the leaderboard.
+pub fn public() -> Document {
+    doc! {
+        "settings.leaderboard": {
+            "$nin": [Visibility::Anonymous.as_str(), Visibility::Hidden.as_str()]
+        }
+    }
+}
+
+fn is_anonymous(user: &Document) -> bool {
+    user.get_document("settings")
+        .and_then(|settings| settings.get_str("leaderboard"))
+        .is_ok_and(|visibility| visibility == Visibility::Anonymous.as_str())
+}
+
+// The rank counts every user, opted out or not, so it's the same as on /profile
+async fn author_place(ctx: &Context<'_>) -> Result<(i64, u64), Error> {
     let db = &ctx.data().db;
     let collection: Collection<Document> = db.collection("users");
 
     let user = collection
         .find_one(doc! {"user_id": ctx.author().id.to_string()})
         .await?;
-    if user.is_none() {
+    let Some(user) = user else {
         return Ok((-1, 0));
-    }
-    let user_counter = user.unwrap().get_i64("counter").unwrap();
-    let cursor = collection
-        .find(doc! {})
-        .sort(doc! { "counter": -1 })
+    };
+    let user_counter = user.get_i64("counter")?;
+    let (place, total) = rank(&collection, user_counter).await?;
+
+    Ok((place as i64, total))
+}
+
+/// Returns the place on the leaderboard for a given score and the total number of users.
+/// Users with the same score share the same place.
+pub async fn rank(collection: &Collection<Document>, counter: i64) -> Result<(u64, u64), Error> {
+    let ahead = collection
+        .count_documents(doc! { "counter": { "$gt": counter } })
         .await?;
-    let users: Vec<Document> = cursor.try_collect().await?;
-    let user_position = users
-        .iter()
-        .position(|x| x.get_i64("counter").unwrap() == user_counter)
-        .unwrap();
+    let total = collection.count_documents(doc! {}).await?;
 
-    Ok((user_position as i64 + 1, users.len()))
+    Ok((ahead + 1, total))
 }
 
-async fn make_embed(ctx: Context<'_>, users: Vec<Document>, filter: Filter) -> CreateEmbed {
+async fn make_embed(
+    ctx: Context<'_>,
+    locale: &str,
+    numbers: NumberFormat,
+    users: Vec<Document>,
+    filter: Filter,
+) -> CreateEmbed {
     // This is to be rearranged later
     let _filter: String = String::from(match filter {
         Filter::ScoreAsc => "",
@@ -157,24 +191,28 @@ async fn make_embed(ctx: Context<'_>, users: Vec<Document>, filter: Filter) -> C
     let footer = match author_place(&ctx).await {
         Ok((place, _)) => {
             if place == -1 {
-                "You are not on the leaderboard.".to_string()
+                tr!(locale, "leaderboard.not_ranked")
             } else {
-                format!("You are #{} on the leaderboard.", place)
+                tr!(locale, "leaderboard.place", place = numbers.number(place))
             }
         }
-        Err(_) => "You are not on the leaderboard.".to_string(),
+        Err(_) => tr!(locale, "leaderboard.not_ranked"),
     };
 
     let footer = CreateEmbedFooter::new(footer);
 
     // Number 1 spot avatar url
-    let thumbnail = match users[0].get_str("avatar_url") {
-        Ok(url) => url.to_string(),
-        Err(_) => ctx.author().default_avatar_url(),
+    let thumbnail = match users
+        .first()
+        .filter(|user| !is_anonymous(user))
+        .and_then(|user| user.get_str("avatar_url").ok())
+    {
+        Some(url) => url.to_string(),
+        None => ctx.author().default_avatar_url(),
     };
 
     let mut embed = CreateEmbed::new()
-        .title("__Leaderboard__")
+        .title(tr!(locale, "leaderboard.title"))
         .color(0x5754d0)
         .footer(footer)
         .thumbnail(thumbnail);
@@ -183,8 +221,12 @@ async fn make_embed(ctx: Context<'_>, users: Vec<Document>, filter: Filter) -> C
 
     // This part should be rewritten later, it's a bit messy
     for (i, user) in users.iter().enumerate() {
-        let username = user.get_str("username").unwrap();
-        let counter = user.get_i64("counter").unwrap();
+        let username = if is_anonymous(user) {
+            tr!(locale, "leaderboard.anonymous", place = i + 1)
+        } else {
+            display_with_handle(user, ctx.guild_id())
+        };
+        let counter = user.get_i64("counter").unwrap_or_default();
 
         let medal = match i {
             0 => "🥇 ",
@@ -193,7 +235,8 @@ async fn make_embed(ctx: Context<'_>, users: Vec<Document>, filter: Filter) -> C
             _ => "",
         };
 
-        users_str.push_str(format!("{}{}: **{}**", medal, username, counter).as_str());
+        users_str
+            .push_str(format!("{}{}: **{}**", medal, username, numbers.score(counter)).as_str());
 
         if i != users.len() - 1 {
             users_str.push('\n');
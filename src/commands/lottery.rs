@@ -0,0 +1,448 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::account::DELETED_USER;
+use crate::commands::{Context, Error};
+use crate::config;
+use crate::error::BotError;
+use crate::format::{NumberFormat, number_format};
+use crate::i18n::{DEFAULT_LOCALE, locale, tr};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{DateTime, Document, doc};
+use mongodb::error::{ErrorKind, WriteFailure};
+use mongodb::options::{IndexOptions, ReturnDocument};
+use mongodb::{Collection, Database, IndexModel};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serenity::all::{ChannelId, CreateEmbedFooter, CreateMessage, Http};
+use serenity::builder::CreateEmbed;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+const TICKET_PRICE: i64 = 10;
+// The draw happens early if the pot reaches this amount
+const POT_THRESHOLD: i64 = 10_000;
+// Time between the opening of a draw and the draw itself. A draw opens on the first /lottery command
+// after the previous one, status included since it shows the seed hash of the open draw.
+const DRAW_INTERVAL: i64 = 24 * 60 * 60 * 1000;
+// How often the background task checks if a draw is due
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Buy tickets and win the pot of the lottery
+#[poise::command(slash_command, subcommands("buy", "status"), subcommand_required)]
+pub async fn lottery(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Buy lottery tickets with your score
+#[poise::command(slash_command)]
+async fn buy(
+    ctx: Context<'_>,
+    #[description = "Number of tickets to buy"]
+    #[min = 1]
+    #[max = 1000]
+    tickets: i64,
+) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let user_id = ctx.author().id.to_string();
+    let cost = tickets * TICKET_PRICE;
+    let locale = locale(ctx).await?;
+    let numbers = number_format(ctx, locale).await?;
+
+    let draw = open_draw(db).await?;
+    let draw_id = draw.get_object_id("_id")?;
+
+    let users: Collection<Document> = db.collection("users");
+    let draws: Collection<Document> = db.collection("lottery_draws");
+    let entries: Collection<Document> = db.collection("lottery_tickets");
+
+    let mut session = db.client().start_session().await?;
+    session.start_transaction().await?;
+
+    let result = users
+        .update_one(
+            doc! { "user_id": &user_id, "counter": { "$gte": cost } },
+            doc! { "$inc": { "counter": -cost } },
+        )
+        .session(&mut session)
+        .await?;
+
+    if result.modified_count == 0 {
+        session.abort_transaction().await?;
+        return Err(BotError::User(tr!(
+            locale,
+            "lottery.not_enough",
+            cost = numbers.number(cost),
+            tickets = numbers.number(tickets)
+        ))
+        .into());
+    }
+
+    // The draw might have been drawn between open_draw and now
+    let result = draws
+        .update_one(
+            doc! { "_id": draw_id, "status": "open" },
+            doc! { "$inc": { "pot": cost, "tickets": tickets } },
+        )
+        .session(&mut session)
+        .await?;
+
+    if result.modified_count == 0 {
+        session.abort_transaction().await?;
+        return Err(BotError::User(tr!(locale, "lottery.draw_ended")).into());
+    }
+
+    entries
+        .update_one(
+            doc! { "draw_id": draw_id, "user_id": &user_id },
+            doc! { "$inc": { "tickets": tickets } },
+        )
+        .upsert(true)
+        .session(&mut session)
+        .await?;
+
+    session.commit_transaction().await?;
+
+    info!("{} bought {} lottery ticket(s)", user_id, tickets);
+
+    let builder = poise::reply::CreateReply::default()
+        .content(tr!(
+            locale,
+            "lottery.bought",
+            tickets = numbers.number(tickets),
+            cost = numbers.number(cost)
+        ))
+        .ephemeral(true);
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// View the current pot and the last draw
+#[poise::command(slash_command)]
+async fn status(ctx: Context<'_>) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let draws: Collection<Document> = db.collection("lottery_draws");
+    let entries: Collection<Document> = db.collection("lottery_tickets");
+    let locale = locale(ctx).await?;
+    let numbers = number_format(ctx, locale).await?;
+
+    let draw = open_draw(db).await?;
+    let own_tickets = entries
+        .find_one(doc! {
+            "draw_id": draw.get_object_id("_id")?,
+            "user_id": ctx.author().id.to_string()
+        })
+        .await?
+        .and_then(|entry| entry.get_i64("tickets").ok())
+        .unwrap_or(0);
+
+    let mut embed = CreateEmbed::new()
+        .title(tr!(locale, "lottery.title"))
+        .description(tr!(
+            locale,
+            "lottery.status",
+            pot = numbers.score(draw.get_i64("pot").unwrap_or(0)),
+            tickets = numbers.number(draw.get_i64("tickets").unwrap_or(0)),
+            own = numbers.number(own_tickets),
+            draw = draw.get_datetime("draw_at")?.timestamp_millis() / 1000,
+            threshold = numbers.score(POT_THRESHOLD),
+            hash = draw.get_str("seed_hash").unwrap_or("-")
+        ))
+        .color(0x5754d0)
+        .footer(CreateEmbedFooter::new(tr!(
+            locale,
+            "lottery.price",
+            price = numbers.number(TICKET_PRICE)
+        )));
+
+    let last = draws
+        .find_one(doc! { "status": "drawn" })
+        .sort(doc! { "drawn_at": -1 })
+        .await?;
+
+    if let Some(last) = last {
+        embed = embed.field(
+            tr!(locale, "lottery.last_draw"),
+            describe_result(locale, numbers, &last),
+            false,
+        );
+    }
+
+    let builder = poise::reply::CreateReply::default().embed(embed);
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Returns the open draw, creating it if needed.
+///
+/// The seed is picked when the draw opens and only its hash is shown until the draw,
+/// so it can't be changed once tickets are sold without the hash giving it away.
+async fn open_draw(db: &Database) -> Result<Document, Error> {
+    let draws: Collection<Document> = db.collection("lottery_draws");
+    let now = DateTime::now();
+    let seed = rand::random::<u64>().to_string();
+    let update = doc! {
+        "$setOnInsert": {
+            "opened_at": now,
+            "draw_at": DateTime::from_millis(now.timestamp_millis() + DRAW_INTERVAL),
+            "pot": 0i64,
+            "tickets": 0i64,
+            "seed_hash": seed_hash(&seed),
+            "seed": seed
+        }
+    };
+
+    let open = || {
+        draws
+            .find_one_and_update(doc! { "status": "open" }, update.clone())
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+    };
+
+    let draw = match open().await {
+        // Another command opened the draw at the same time, the unique index kept only theirs
+        Err(e) if is_duplicate_key(&e) => open().await?,
+        result => result?,
+    };
+
+    draw.ok_or_else(|| "Failed to open a lottery draw".into())
+}
+
+/// Ensures there's never more than one open draw, concurrent commands could each open one otherwise.
+pub async fn create_indexes(db: &Database) -> Result<(), mongodb::error::Error> {
+    let draws: Collection<Document> = db.collection("lottery_draws");
+    let options = IndexOptions::builder()
+        .name(String::from("one_open_draw"))
+        .unique(true)
+        .partial_filter_expression(doc! { "status": "open" })
+        .build();
+
+    draws
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "status": 1 })
+                .options(options)
+                .build(),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn is_duplicate_key(error: &mongodb::error::Error) -> bool {
+    const DUPLICATE_KEY: i32 = 11000;
+    match &*error.kind {
+        ErrorKind::Command(error) => error.code == DUPLICATE_KEY,
+        ErrorKind::Write(WriteFailure::WriteError(error)) => error.code == DUPLICATE_KEY,
+        _ => false,
+    }
+}
+
+/// SHA-256 of the seed in hex, the same as `echo -n <seed> | sha256sum`.
+fn seed_hash(seed: &str) -> String {
+    Sha256::digest(seed.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn describe_result(locale: &str, numbers: NumberFormat, draw: &Document) -> String {
+    match draw.get_str("winner_id") {
+        Ok(winner) => tr!(
+            locale,
+            "lottery.result",
+            winner = if winner == DELETED_USER {
+                tr!(locale, "lottery.deleted_winner")
+            } else {
+                format!("<@{}>", winner)
+            },
+            pot = numbers.score(draw.get_i64("pot").unwrap_or(0)),
+            ticket = numbers.number(draw.get_i64("winning_ticket").unwrap_or(0) + 1),
+            tickets = numbers.number(draw.get_i64("tickets").unwrap_or(0)),
+            seed = draw.get_str("seed").unwrap_or_default(),
+            hash = draw.get_str("seed_hash").unwrap_or_default()
+        ),
+        Err(_) => tr!(locale, "lottery.no_tickets"),
+    }
+}
+
+/// Background task drawing the lottery once it's due.
+pub async fn run_draws(db: Database, http: Arc<Http>) {
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        if let Err(e) = draw_if_due(&db, &http).await {
+            warn!("Lottery draw failed: {}", e);
+        }
+    }
+}
+
+async fn draw_if_due(db: &Database, http: &Http) -> Result<(), Error> {
+    let draws: Collection<Document> = db.collection("lottery_draws");
+
+    // Draws are only done by this task, one at a time, so a draw still "drawing" here was
+    // interrupted by an error or a restart and is picked up again
+    let draw = match draws.find_one(doc! { "status": "drawing" }).await? {
+        Some(draw) => {
+            warn!(
+                "Resuming unfinished lottery draw {}",
+                draw.get_object_id("_id")?
+            );
+            Some(draw)
+        }
+        // Switching the status first makes sure no ticket is bought while drawing
+        None => {
+            draws
+                .find_one_and_update(
+                    doc! {
+                        "status": "open",
+                        "$or": [
+                            { "draw_at": { "$lte": DateTime::now() } },
+                            { "pot": { "$gte": POT_THRESHOLD } }
+                        ]
+                    },
+                    doc! { "$set": { "status": "drawing" } },
+                )
+                .return_document(ReturnDocument::After)
+                .await?
+        }
+    };
+
+    let Some(draw) = draw else {
+        return Ok(());
+    };
+
+    let draw = draw_winner(db, draw).await?;
+
+    // The log channel isn't tied to a user, it gets the default full format
+    let numbers = NumberFormat::new(DEFAULT_LOCALE, false);
+
+    info!(
+        "Lottery drawn: {}",
+        describe_result(DEFAULT_LOCALE, numbers, &draw)
+    );
+
+    if let Some(channel) = config::dynamic().log_channel_id {
+        let embed = CreateEmbed::new()
+            .title(tr!(DEFAULT_LOCALE, "lottery.draw_title"))
+            .description(describe_result(DEFAULT_LOCALE, numbers, &draw))
+            .color(0x5754d0);
+        ChannelId::new(channel)
+            .send_message(http, CreateMessage::new().embed(embed))
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Picks the winner of a draw and pays them.
+/// The payout and the end of the draw are written in one transaction, so a draw is either
+/// still "drawing" (and resumed on the next check) or paid and "drawn".
+///
+/// The draw is reproducible from the stored record: entries are sorted by _id (the order of
+/// each user's first purchase), each user owning a contiguous range of tickets in that order, and the winning ticket is
+/// `ChaCha8Rng::seed_from_u64(seed).gen_range(0..tickets)`. The seed is revealed here,
+/// its SHA-256 matching the hash shown while the draw was open.
+async fn draw_winner(db: &Database, draw: Document) -> Result<Document, Error> {
+    let draws: Collection<Document> = db.collection("lottery_draws");
+    let entries: Collection<Document> = db.collection("lottery_tickets");
+    let users: Collection<Document> = db.collection("users");
+    let draw_id = draw.get_object_id("_id")?;
+
+    let entries: Vec<Document> = entries
+        .find(doc! { "draw_id": draw_id })
+        .sort(doc! { "_id": 1 })
+        .await?
+        .try_collect()
+        .await?;
+
+    let total: i64 = entries
+        .iter()
+        .map(|entry| entry.get_i64("tickets").unwrap_or(0))
+        .sum();
+
+    let mut update = doc! {
+        "status": "drawn",
+        "drawn_at": DateTime::now(),
+        "tickets": total
+    };
+
+    let seed = match draw.get_str("seed").ok().and_then(|seed| seed.parse().ok()) {
+        Some(seed) => seed,
+        None => {
+            // Draws opened before seeds were committed to don't have one yet
+            warn!("Lottery draw {} has no committed seed", draw_id);
+            let seed: u64 = rand::random();
+            update.insert("seed", seed.to_string());
+            update.insert("seed_hash", seed_hash(&seed.to_string()));
+            seed
+        }
+    };
+
+    let mut session = db.client().start_session().await?;
+    session.start_transaction().await?;
+
+    if total > 0 {
+        let winning_ticket = ChaCha8Rng::seed_from_u64(seed).gen_range(0..total);
+
+        let mut upper = 0;
+        let winner = entries
+            .iter()
+            .find(|entry| {
+                upper += entry.get_i64("tickets").unwrap_or(0);
+                winning_ticket < upper
+            })
+            .and_then(|entry| entry.get_str("user_id").ok())
+            .ok_or("Winning ticket out of range")?;
+
+        users
+            .update_one(
+                doc! { "user_id": winner },
+                doc! { "$inc": { "counter": draw.get_i64("pot").unwrap_or(0) } },
+            )
+            .session(&mut session)
+            .await?;
+
+        update.insert("winner_id", winner);
+        update.insert("winning_ticket", winning_ticket);
+    }
+
+    let draw = draws
+        .find_one_and_update(
+            doc! { "_id": draw_id, "status": "drawing" },
+            doc! { "$set": update },
+        )
+        .return_document(ReturnDocument::After)
+        .session(&mut session)
+        .await?;
+
+    let Some(draw) = draw else {
+        session.abort_transaction().await?;
+        return Err("Lottery draw disappeared or was already drawn".into());
+    };
+
+    session.commit_transaction().await?;
+
+    Ok(draw)
+}
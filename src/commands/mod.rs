@@ -17,41 +17,198 @@
  */
 
 pub mod about;
+pub mod account;
+pub mod admin;
+pub mod compare;
+pub mod duel;
+pub mod event;
+pub mod give;
 pub mod help;
+pub mod history;
 pub mod info;
 pub mod leaderboard;
+pub mod lottery;
 pub mod ping;
 pub mod play;
 pub mod profile;
+pub mod quests;
+pub mod settings;
 pub mod sync;
 
+use serenity::all::GuildId;
+
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Context<'a> = poise::Context<'a, Data, Error>;
 
 pub struct Data {
     pub db: mongodb::Database,
     pub uptime: std::time::Instant,
+    pub sessions: std::sync::Arc<crate::shutdown::Sessions>,
+}
+
+/// Nickname of a user record in a guild, if they have one there.
+pub fn stored_nickname(user: &mongodb::bson::Document, guild_id: GuildId) -> Option<&str> {
+    user.get_document("nicknames")
+        .ok()?
+        .get_str(guild_id.to_string())
+        .ok()
+}
+
+/// Adds the lowercase copy under "search" of every name field set by `names`
+/// ("username", "global_name" or "nicknames.<guild_id>"), which the name search queries.
+pub fn with_search_names(mut names: mongodb::bson::Document) -> mongodb::bson::Document {
+    use mongodb::bson::Bson;
+
+    let copies: Vec<(String, Bson)> = names
+        .iter()
+        .filter(|(key, _)| {
+            *key == "username" || *key == "global_name" || key.starts_with("nicknames.")
+        })
+        .map(|(key, value)| {
+            let copy = match value {
+                Bson::String(name) => Bson::String(name.to_lowercase()),
+                other => other.clone(),
+            };
+            (format!("search.{}", key), copy)
+        })
+        .collect();
+
+    names.extend(copies);
+    names
+}
+
+/// Name shown for a user record: their nickname in the guild, then their display name, then their username.
+pub fn display_name(user: &mongodb::bson::Document, guild_id: Option<GuildId>) -> &str {
+    guild_id
+        .and_then(|guild_id| stored_nickname(user, guild_id))
+        .or_else(|| user.get_str("global_name").ok())
+        .unwrap_or_else(|| user.get_str("username").unwrap_or_default())
+}
+
+/// Display name of a user record followed by their username, which is left out if it's the same.
+pub fn display_with_handle(user: &mongodb::bson::Document, guild_id: Option<GuildId>) -> String {
+    let name = display_name(user, guild_id);
+    match user.get_str("username") {
+        Ok(handle) if handle != name => format!("{} (@{})", name, handle),
+        _ => name.to_string(),
+    }
+}
+
+/// Number of days since the unix epoch, days change at midnight UTC.
+pub fn utc_day(date: mongodb::bson::DateTime) -> i64 {
+    date.timestamp_millis().div_euclid(86_400_000)
 }
 
 /*
     The database schema is as follows:
         user_id: String,
         username: String,
+        global_name: String (optional),
+        nicknames: { <guild_id>: String } (optional),
+        search: { username: String, global_name: String, nicknames: { <guild_id>: String } }
+            (lowercase copies of the names above, see with_search_names),
         avatar_url: String,
-        counter: i64
+        counter: i64,
+        created_at: DateTime,
+        total_clicks: i64,
+        event_quest_points: i64,
+        sessions: i64,
+        longest_session_secs: i64 (optional),
+        best_cpm: f64 (optional),
+        duel_wins: i64 (optional),
+        duel_losses: i64 (optional),
+        transfer_day: i64 (optional),
+        transferred_today: i64 (optional),
+        settings: {
+            locale: String,
+            number_format: String,
+            ephemeral_sessions: bool,
+            leaderboard: String,
+            session_timeout_mins: i64,
+            button_emoji: String
+        } (optional, every field is optional too)
 
     user_id uses a string because Discord stores its user IDs as a snowflake, which is a 64-bit integer.
     However, that integer is unsigned, which mongodb doesn't support.
     In addition, making it a String will make it sure that there won't be any problem.
 
-    username is used to search for a user when doing a /profile command, it's also used for the leaderboard.
-    there might be an issue if a user changes their username, and doesn't update their score, since it won't be synced in the database anymore.
-    This can cause issues with the /profile not working as expected,
-    but that's a minor issue which might be fixed in the future. (TODO)
+    username is used to search for a user when doing a /profile command with a username, it's also used for the leaderboard.
+    username, global_name (the display name set on the Discord account) and avatar_url are refreshed from the member update
+    events of the gateway (see handler/member_update.rs), so they only go stale for users who share no server with the bot.
+    nicknames holds the nickname of the user in each guild they were seen in, a null value meaning they have none there.
+    Leaderboards and profiles show the nickname of the current guild, then global_name, then username (see display_name),
+    and /profile can find a user by any of them. /sync is still there to force a refresh. /profile prefers looking up by user_id (when given a user instead of a username),
+    which isn't affected by this.
 
     counter uses an i64 because its limit are big enough to not have to worry about it.
     In the old versions of Button Clicker (v1 & v2), the counter was a string, which allowed to store theoretically an infinite number.
     But it was recently found that after 5 years, it didn't really matter because the counter hardly exceeded multiple thousands.
     An i32 was ignored because it doesn't cost much to use 64 bits instead + it's more future-proof as future version of this bot,
     might have new features like a multiplier that could make the counter exceed the limit of an i32. (probably not, but we never know)
+
+    created_at, total_clicks, event_quest_points and sessions were added later on, older accounts don't have them.
+    For these accounts the creation date comes from the _id ObjectId and the other stats are read as 0.
+    event_quest_points is what event multipliers and quest rewards added to the counter on top of the clicks.
+    Gifts, duels and the lottery also change the counter but aren't counted in it.
+    longest_session_secs and best_cpm are updated when a /play session ends, a session lasting until its last click.
+    best_cpm (clicks per minute) is only set if the clicks of the session spanned at least a minute.
+
+    duel_wins and duel_losses are only set once the user has finished a /duel, they should be read as 0 when missing.
+    Every finished duel is also stored in the "duels" collection, without the ID of a player who deleted their account.
+
+    transfer_day is the UTC day (see utc_day) of the last /give, transferred_today is the amount sent that day.
+    They're used to enforce the daily transfer limit. Every transfer is stored twice in the "transfers" collection,
+    once for each party, so a user's history is a simple query on user_id.
+
+    settings holds the preferences chosen with /settings, see settings::Settings for the defaults of missing fields.
+    locale is the language the bot answers in, when it's missing the language of the user's Discord client is used instead.
+    number_format is "full" or "short" (1.2K). leaderboard is "visible", "anonymous" (listed without their name and avatar)
+    or "hidden" (not listed), in both private cases /profile can't find them by name. button_emoji is either a unicode emoji or a custom one (<:name:id>).
+
+    The "quests" collection holds one document per user and per quest period:
+        user_id: String,
+        period: String ("daily" or "weekly"),
+        period_id: i64 (UTC day or week number),
+        clicks: i64,
+        guilds: [String],
+        longest_session_secs: i64,
+        claimed: [String] (ids of the claimed quests)
+    Quests themselves are defined in data/quests.ron, the active ones are picked from the period_id
+    so a new document (and new quests) is used as soon as the day or week changes.
+
+    The "events" collection holds the bonus events scheduled with /event:
+        name: String,
+        kind: String ("multiplier" or "lucky"),
+        multiplier: i64,
+        chance: f64 (only used by lucky events),
+        start: DateTime,
+        end: DateTime
+    When several events overlap, the one with the highest multiplier wins.
+
+    The lottery uses two collections. "lottery_draws" holds one document per draw:
+        status: String ("open", "drawing" or "drawn", a partial unique index allows only one open draw),
+        opened_at: DateTime,
+        draw_at: DateTime,
+        pot: i64,
+        tickets: i64,
+        seed: String (u64 used to seed the RNG, picked when the draw opens and kept secret until drawn),
+        seed_hash: String (hex SHA-256 of seed, shown while the draw is open),
+        winner_id: String (set once drawn, missing if nobody bought a ticket),
+        winning_ticket: i64 (0-based, set once drawn),
+        drawn_at: DateTime
+    and "lottery_tickets" holds how many tickets each user bought for a draw:
+        draw_id: ObjectId,
+        user_id: String,
+        tickets: i64
+    The tickets of a draw are numbered in _id order, which is why /account delete only replaces the user_id
+    of drawn tickets with "deleted" instead of removing them.
+
+    The "history" collection holds snapshots of the scores, taken every hour for the scores that changed:
+        user_id: String,
+        counter: i64,
+        at: DateTime
+    The user record keeps the score of its last snapshot in snapshot_counter to know if it changed.
+
+    /account export and /account delete go through every collection above, any new collection holding
+    user data has to be added to them.
 */
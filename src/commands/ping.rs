@@ -17,15 +17,17 @@
  */
 
 use crate::commands::{Context, Error};
+use crate::i18n::{locale, tr};
 use serenity::builder::CreateEmbed;
 
 /// Measure the latency of the bot.
 #[poise::command(slash_command)]
 pub async fn ping(ctx: Context<'_>) -> Result<(), Error> {
+    let locale = locale(ctx).await?;
     let latency = ctx.ping().await.as_millis();
     let embed = CreateEmbed::new()
-        .title("Pong!")
-        .description(format!("Latency: {}ms", latency))
+        .title(tr!(locale, "ping.title"))
+        .description(tr!(locale, "ping.latency", latency = latency))
         .color(0x5754d0);
 
     let builder = poise::reply::CreateReply::default()
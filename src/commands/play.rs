@@ -16,19 +16,42 @@
  *  as defined by the AGPLv3 license.
  */
 
-use crate::commands::{Context, Error};
+use crate::commands::event::{Event, active_event};
+use crate::commands::quests::{record_click, record_session};
+use crate::commands::settings::Settings;
+use crate::commands::{Context, Error, stored_nickname, with_search_names};
+use crate::error::BotError;
+use crate::format::NumberFormat;
+use crate::i18n::{locale_for, tr};
+use crate::metrics::METRICS;
 use mongodb::Collection;
-use mongodb::bson::{Document, doc};
-use serenity::all::{CreateEmbedFooter, CreateInteractionResponse, EditMessage};
+use mongodb::bson::{DateTime, Document, doc};
+use poise::ReplyHandle;
+use serenity::all::{
+    ButtonStyle, CreateActionRow, CreateButton, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseMessage, EditMessage, Message,
+};
 use serenity::builder::CreateEmbed;
-use std::time::Duration;
-use tracing::info;
+use std::future::IntoFuture;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+// The best clicks per minute is only recorded if the clicks span at least this long
+const CPM_MIN_SPAN: Duration = Duration::from_secs(60);
 
 /// Create a play session
 #[poise::command(slash_command)]
 pub async fn play(ctx: Context<'_>) -> Result<(), Error> {
-    let time = std::time::Instant::now();
+    let time = Instant::now();
     let db = &ctx.data().db;
+    let settings = Settings::load(ctx).await?;
+    let locale = locale_for(ctx, &settings);
+    let numbers = NumberFormat::new(locale, settings.short_numbers);
+
+    // No new sessions while shutting down, they would be cut right away
+    let Some(mut session) = ctx.data().sessions.open() else {
+        return Err(BotError::User(tr!(locale, "play.restarting")).into());
+    };
 
     // Checks if the user has an account
     // It creates a new account if the user doesn't have one
@@ -37,23 +60,20 @@ pub async fn play(ctx: Context<'_>) -> Result<(), Error> {
         .find_one(doc! {"user_id": ctx.author().id.to_string()})
         .await?;
     let mut counter: i64;
-    if user.is_none() {
-        create_user(ctx, &collection_user).await?;
-        counter = 0;
-    } else {
-        counter = user.clone().unwrap().get_i64("counter").unwrap();
+    if let Some(user) = user {
+        counter = user.get_i64("counter")?;
 
         // Update the username in the database if it's different
-        if user.clone().unwrap().get_str("username").unwrap() != ctx.author().name {
+        if user.get_str("username").unwrap_or_default() != ctx.author().name {
             collection_user
                 .update_one(
                     doc! {
                         "user_id": ctx.author().id.to_string()
                     },
                     doc! {
-                        "$set": {
+                        "$set": with_search_names(doc! {
                             "username": ctx.author().name.clone()
-                        }
+                        })
                     },
                 )
                 .await?;
@@ -61,7 +81,7 @@ pub async fn play(ctx: Context<'_>) -> Result<(), Error> {
         }
 
         // Update the avatar url in the database if it's different
-        if user.unwrap().get_str("avatar_url").unwrap()
+        if user.get_str("avatar_url").unwrap_or_default()
             != ctx.author().avatar_url().unwrap_or_default()
         {
             collection_user
@@ -78,32 +98,82 @@ pub async fn play(ctx: Context<'_>) -> Result<(), Error> {
                 .await?;
             info!("Updated avatar url for {}", ctx.author().id);
         }
+
+        // Update the display name and the nickname in this guild in the database if they're different
+        let mut names = doc! {};
+        if user.get_str("global_name").ok() != ctx.author().global_name.as_deref() {
+            names.insert("global_name", ctx.author().global_name.clone());
+        }
+        if let Some(guild_id) = ctx.guild_id() {
+            let nickname = ctx
+                .author_member()
+                .await
+                .and_then(|member| member.nick.clone());
+            if nickname.as_deref() != stored_nickname(&user, guild_id) {
+                names.insert(format!("nicknames.{}", guild_id), nickname);
+            }
+        }
+        if !names.is_empty() {
+            collection_user
+                .update_one(
+                    doc! {
+                        "user_id": ctx.author().id.to_string()
+                    },
+                    doc! {
+                        "$set": with_search_names(names)
+                    },
+                )
+                .await?;
+            info!("Updated display names for {}", ctx.author().id);
+        }
+    } else {
+        create_user(ctx, &collection_user).await?;
+        counter = 0;
     }
 
     info!("Creating a new session for {}", ctx.author().id);
 
-    let embed = make_embed(ctx, counter);
+    collection_user
+        .update_one(
+            doc! { "user_id": ctx.author().id.to_string() },
+            doc! { "$inc": { "sessions": 1i64 } },
+        )
+        .await?;
+
+    // Used for the best clicks per minute
+    let mut clicks: i64 = 0;
+    let mut first_click: Option<Instant> = None;
+    let mut last_click: Option<Instant> = None;
+
+    let embed = make_embed(
+        ctx,
+        locale,
+        numbers,
+        counter,
+        active_event(db).await?.as_ref(),
+    );
 
     let builder = poise::reply::CreateReply::default()
         .embed(embed)
-        .components(vec![poise::serenity_prelude::CreateActionRow::Buttons(
-            vec![
-                poise::serenity_prelude::CreateButton::new("click")
-                    .label("🔘")
-                    .style(poise::serenity_prelude::ButtonStyle::Primary),
-                poise::serenity_prelude::CreateButton::new("delete")
-                    .label("✖️")
-                    .style(poise::serenity_prelude::ButtonStyle::Danger),
-            ],
-        )]);
-
-    let msg = ctx.send(builder).await?.into_message().await?;
+        .ephemeral(settings.ephemeral_sessions)
+        .components(buttons(&settings, false));
+
+    // Ephemeral messages can only be edited and deleted through an interaction, so the handle is kept
+    let reply = ctx.send(builder).await?;
+    let msg = reply.message().await?;
+    let timeout = Duration::from_secs(settings.session_timeout_mins as u64 * 60);
 
     loop {
-        let interaction = msg
-            .await_component_interactions(ctx)
-            .timeout(Duration::from_secs(3600))
-            .await;
+        let collector = msg.await_component_interactions(ctx).timeout(timeout);
+        let interaction = tokio::select! {
+            interaction = collector.into_future() => interaction,
+            () = session.closed() => {
+                let embed = make_embed(ctx, locale, numbers, counter, None)
+                    .field(tr!(locale, "play.ended"), tr!(locale, "play.restarting"), false);
+                end_for_shutdown(ctx, &reply, &msg, &settings, embed).await;
+                break;
+            }
+        };
 
         match interaction {
             Some(interaction) => {
@@ -116,25 +186,39 @@ pub async fn play(ctx: Context<'_>) -> Result<(), Error> {
                     continue;
                 }
 
-                let interaction_time = std::time::Instant::now();
+                let interaction_time = Instant::now();
 
                 // Delete the message if the user clicks on the stop session button
                 if interaction.data.custom_id.as_str() != "click" {
-                    msg.delete(ctx).await?;
+                    interaction
+                        .create_response(ctx, CreateInteractionResponse::Acknowledge)
+                        .await?;
+                    interaction.delete_response(ctx).await?;
                     break;
                 }
 
-                increase_counter(ctx, &collection_user, &mut counter).await?;
+                let event = active_event(db).await?;
+                increase_counter(ctx, &collection_user, &mut counter, event.as_ref()).await?;
+                record_click(db, &ctx.author().id.to_string(), ctx.guild_id()).await?;
 
-                let mut new_msg = interaction.message.clone();
-                new_msg
-                    .edit(ctx, EditMessage::new().embed(make_embed(ctx, counter)))
-                    .await?;
+                clicks += 1;
+                first_click.get_or_insert(interaction_time);
+                last_click = Some(interaction_time);
 
+                let embed = make_embed(ctx, locale, numbers, counter, event.as_ref());
                 interaction
-                    .create_response(ctx, CreateInteractionResponse::Acknowledge)
+                    .create_response(
+                        ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new().embed(embed),
+                        ),
+                    )
                     .await?;
 
+                METRICS.clicks.inc();
+                METRICS
+                    .click_duration
+                    .observe(interaction_time.elapsed().as_secs_f64());
                 info!(
                     "Increase Counter for {} | Time: {:?}",
                     ctx.author().id.to_string(),
@@ -143,12 +227,36 @@ pub async fn play(ctx: Context<'_>) -> Result<(), Error> {
             }
 
             None => {
-                msg.delete(ctx).await?;
+                if settings.ephemeral_sessions {
+                    // The token of the command may have expired, the message then stays until it's dismissed
+                    let _ = reply.delete(ctx).await;
+                } else {
+                    msg.delete(ctx).await?;
+                }
                 break;
             }
         }
     }
 
+    // The session only counts until the last click, an abandoned one would otherwise last until the timeout.
+    // This applies to the quests and to the longest session alike.
+    let active = last_click.map_or(Duration::ZERO, |last| last - time);
+    record_session(db, &ctx.author().id.to_string(), active).await?;
+
+    let mut stats = doc! { "longest_session_secs": active.as_secs() as i64 };
+    if let (Some(first), Some(last)) = (first_click, last_click) {
+        let span = last - first;
+        if span >= CPM_MIN_SPAN {
+            stats.insert("best_cpm", clicks as f64 / span.as_secs_f64() * 60.0);
+        }
+    }
+    collection_user
+        .update_one(
+            doc! { "user_id": ctx.author().id.to_string() },
+            doc! { "$max": stats },
+        )
+        .await?;
+
     info!(
         "Terminating session for {} | Time {:?}",
         ctx.author().id,
@@ -157,18 +265,81 @@ pub async fn play(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
-fn make_embed(ctx: Context<'_>, counter: i64) -> CreateEmbed {
+fn buttons(settings: &Settings, disabled: bool) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new("click")
+            .emoji(settings.button_emoji())
+            .style(ButtonStyle::Primary)
+            .disabled(disabled),
+        CreateButton::new("delete")
+            .label("✖️")
+            .style(ButtonStyle::Danger)
+            .disabled(disabled),
+    ])]
+}
+
+// Leaves the message in place with its buttons disabled, so it doesn't look like the bot stopped responding
+async fn end_for_shutdown(
+    ctx: Context<'_>,
+    reply: &ReplyHandle<'_>,
+    msg: &Message,
+    settings: &Settings,
+    embed: CreateEmbed,
+) {
+    let result = if settings.ephemeral_sessions {
+        // Only possible through the command's token, which expires after 15 minutes
+        let builder = poise::reply::CreateReply::default()
+            .embed(embed)
+            .components(buttons(settings, true));
+        reply.edit(ctx, builder).await
+    } else {
+        let builder = EditMessage::new()
+            .embed(embed)
+            .components(buttons(settings, true));
+        msg.channel_id
+            .edit_message(ctx, msg.id, builder)
+            .await
+            .map(|_| ())
+    };
+
+    if let Err(e) = result {
+        warn!("Failed to end the session of {}: {}", ctx.author().id, e);
+    }
+}
+
+fn make_embed(
+    ctx: Context<'_>,
+    locale: &str,
+    numbers: NumberFormat,
+    counter: i64,
+    event: Option<&Event>,
+) -> CreateEmbed {
     let thumbnail = match ctx.author().avatar_url() {
         Some(url) => url,
         None => ctx.author().default_avatar_url(),
     };
-    let footer = CreateEmbedFooter::new("Click the button to increase your score!");
-    CreateEmbed::new()
-        .title(format!("__{}'s session__", ctx.author().name))
-        .description(format!("Current Score: **{}**", counter))
+    let footer = CreateEmbedFooter::new(tr!(locale, "play.footer"));
+    let mut embed = CreateEmbed::new()
+        .title(tr!(locale, "play.title", name = ctx.author().name))
+        .description(tr!(locale, "play.score", score = numbers.score(counter)))
         .color(0x5754d0)
         .thumbnail(thumbnail)
-        .footer(footer)
+        .footer(footer);
+
+    if let Some(event) = event {
+        embed = embed.field(
+            tr!(locale, "play.event"),
+            tr!(
+                locale,
+                "play.event_ends",
+                event = event.summary(locale),
+                end = event.end.timestamp_millis() / 1000
+            ),
+            false,
+        );
+    }
+
+    embed
 }
 
 pub async fn create_user(ctx: Context<'_>, collection: &Collection<Document>) -> Result<(), Error> {
@@ -176,8 +347,17 @@ pub async fn create_user(ctx: Context<'_>, collection: &Collection<Document>) ->
         .insert_one(doc! {
             "user_id": ctx.author().id.to_string(),
             "username": ctx.author().name.clone(),
+            "global_name": ctx.author().global_name.clone(),
+            "search": {
+                "username": ctx.author().name.to_lowercase(),
+                "global_name": ctx.author().global_name.as_deref().map(str::to_lowercase)
+            },
             "avatar_url": ctx.author().avatar_url().unwrap_or_default(),
-            "counter": 0i64
+            "counter": 0i64,
+            "created_at": DateTime::now(),
+            "total_clicks": 0i64,
+            "event_quest_points": 0i64,
+            "sessions": 0i64
         })
         .await?;
 
@@ -188,7 +368,11 @@ async fn increase_counter(
     ctx: Context<'_>,
     collection: &Collection<Document>,
     counter: &mut i64,
+    event: Option<&Event>,
 ) -> Result<(), Error> {
+    // A running event can give more than one point per click
+    let points = event.map_or(1, Event::roll_points);
+
     collection
         .update_one(
             doc! {
@@ -196,7 +380,9 @@ async fn increase_counter(
             },
             doc! {
                 "$inc": {
-                    "counter": 1
+                    "counter": points,
+                    "total_clicks": 1i64,
+                    "event_quest_points": points - 1
                 }
             },
         )
@@ -212,9 +398,9 @@ async fn fetch_score(ctx: Context<'_>, collection: &Collection<Document>) -> Res
     let user = collection
         .find_one(doc! {"user_id": ctx.author().id.to_string()})
         .await?;
-    if user.is_none() {
+    let Some(user) = user else {
         return Ok(0);
-    }
-    let user_counter = user.unwrap().get_i64("counter").unwrap();
+    };
+    let user_counter = user.get_i64("counter")?;
     Ok(user_counter)
 }
@@ -16,80 +16,337 @@
  *  as defined by the AGPLv3 license.
  */
 
-use crate::commands::{Context, Error};
-use mongodb::Collection;
-use mongodb::bson::{Document, doc};
+use crate::commands::leaderboard::{public, rank};
+use crate::commands::{Context, Error, display_name, display_with_handle, with_search_names};
+use crate::error::BotError;
+use crate::format::number_format;
+use crate::i18n::{locale, tr};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{Bson, Document, Regex, doc};
+use mongodb::{Collection, Database, IndexModel};
+use serenity::all::{AutocompleteChoice, User};
 use serenity::builder::CreateEmbed;
+use std::time::Instant;
+use tracing::info;
+
+// Maximum number of usernames suggested by the autocomplete and the "did you mean"
+const MAX_SUGGESTIONS: i64 = 5;
 
 /// View the profile of yourself or a user
 #[poise::command(slash_command)]
 pub async fn profile(
     ctx: Context<'_>,
-    #[description = "(Optional) The username to view the profile of"] username: Option<String>,
+    #[description = "(Optional) The user to view the profile of"] user: Option<User>,
+    #[description = "(Optional) The username or display name to view the profile of"]
+    #[autocomplete = "autocomplete_username"]
+    username: Option<String>,
 ) -> Result<(), Error> {
     let db = &ctx.data().db;
     let collection: Collection<Document> = db.collection("users");
+    let locale = locale(ctx).await?;
 
-    let search_self: bool;
-    let username = match username {
-        Some(username) => {
-            search_self = false;
-            username
-        }
-        None => {
-            search_self = true;
-            ctx.author().name.clone()
+    // Searching by ID is always preferred since a username can change at any time.
+    // This avoids their account to look "broken" too!
+    let user = match (user, username) {
+        (Some(user), _) => find_by_id(&collection, &user.id.to_string()).await?,
+        (None, Some(username)) => {
+            // Usernames are unique so they're tried first, display names and nicknames can be shared
+            let mut user = collection
+                .find_one(name_filter(&["username"], &username))
+                .await?;
+            if user.is_none() {
+                user = collection
+                    .find_one(name_filter(&name_fields(ctx), &username))
+                    .sort(doc! { "counter": -1 })
+                    .await?;
+            }
+
+            if user.is_none() {
+                let suggestions = search_names(ctx, &collection, &username).await?;
+                if suggestions.is_empty() {
+                    return Err(BotError::User(tr!(locale, "error.user_not_found")).into());
+                }
+                let suggestions: Vec<String> = suggestions
+                    .iter()
+                    .map(|user| display_with_handle(user, ctx.guild_id()))
+                    .collect();
+                return Err(BotError::User(tr!(
+                    locale,
+                    "profile.did_you_mean",
+                    suggestions = suggestions.join(", ")
+                ))
+                .into());
+            }
+
+            user
         }
+        (None, None) => find_by_id(&collection, &ctx.author().id.to_string()).await?,
     };
 
-    // If the user isn't using a username, then it'll search itself with the author's ID
-    // This can avoid some bug like searching for a user with a username that doesn't exist
-    // since they might have changed username right before and the bot hasn't updated yet.
-    // This avoids their account to look "broken" too!
-    let user = if search_self {
-        collection
-            .find_one(doc! {
-                "user_id": ctx.author().id.to_string()
-            })
-            .await?
-    } else {
-        collection
-            .find_one(doc! {
-                "username": username
-            })
-            .await?
+    let Some(user) = user else {
+        return Err(BotError::User(tr!(locale, "error.user_not_found")).into());
     };
 
-    if user.is_none() {
-        return Err("User not found".into());
-    }
+    let embed = make_embed(ctx, user).await?;
 
-    let user = user.unwrap();
+    let builder = poise::reply::CreateReply::default().embed(embed);
 
-    let embed = make_embed(ctx, user);
+    ctx.send(builder).await?;
 
-    let builder = poise::reply::CreateReply::default().embed(embed);
+    Ok(())
+}
+
+/// View the Button Clicker profile of a member
+#[poise::command(context_menu_command = "View Button Clicker profile")]
+pub async fn view_profile(ctx: Context<'_>, user: User) -> Result<(), Error> {
+    let collection: Collection<Document> = ctx.data().db.collection("users");
+
+    let Some(user) = find_by_id(&collection, &user.id.to_string()).await? else {
+        let locale = locale(ctx).await?;
+        return Err(BotError::User(tr!(locale, "error.no_account", name = user.name)).into());
+    };
+
+    let builder = poise::reply::CreateReply::default().embed(make_embed(ctx, user).await?);
 
     ctx.send(builder).await?;
 
     Ok(())
 }
 
-fn make_embed(ctx: Context<'_>, user: Document) -> CreateEmbed {
-    // As for now, there will only be a thumbnail if the user views their own profile.
-    let search_user_name = user.get_str("username").unwrap();
-    let thumbnail = if search_user_name == ctx.author().name {
+pub async fn find_by_id(
+    collection: &Collection<Document>,
+    user_id: &str,
+) -> Result<Option<Document>, Error> {
+    let user = collection
+        .find_one(doc! {
+            "user_id": user_id
+        })
+        .await?;
+
+    Ok(user)
+}
+
+async fn autocomplete_username(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let collection: Collection<Document> = ctx.data().db.collection("users");
+
+    // The username is the value since it's unique, the display name is only shown
+    search_names(ctx, &collection, partial)
+        .await
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|user| {
+            let username = user.get_str("username").ok()?;
+            Some(AutocompleteChoice::new(
+                display_with_handle(user, ctx.guild_id()),
+                username,
+            ))
+        })
+        .collect()
+}
+
+/// Fields a player can be looked up by: their username, display name, and nickname in the current guild.
+fn name_fields(ctx: Context<'_>) -> Vec<String> {
+    let mut fields = vec!["username".to_string(), "global_name".to_string()];
+    if let Some(guild_id) = ctx.guild_id() {
+        fields.push(format!("nicknames.{}", guild_id));
+    }
+    fields
+}
+
+/// Matches the users having `value` in one of `fields`.
+/// Players who opted out of the leaderboard can't be found by name.
+fn name_filter(fields: &[impl AsRef<str>], value: impl Into<Bson> + Clone) -> Document {
+    let alternatives: Vec<Document> = fields
+        .iter()
+        .map(|field| doc! { field.as_ref(): value.clone().into() })
+        .collect();
+
+    let mut filter = public();
+    filter.insert("$or", alternatives);
+    filter
+}
+
+/// Users whose name starts with `search`, ignoring the case.
+/// It queries the lowercase copies of the names with an anchored regex, so the index is used.
+async fn search_names(
+    ctx: Context<'_>,
+    collection: &Collection<Document>,
+    search: &str,
+) -> Result<Vec<Document>, Error> {
+    let fields: Vec<String> = name_fields(ctx)
+        .iter()
+        .map(|field| format!("search.{}", field))
+        .collect();
+    let regex = Regex {
+        pattern: format!("^{}", escape_regex(&search.to_lowercase())),
+        options: String::new(),
+    };
+
+    let users = collection
+        .find(name_filter(&fields, regex))
+        .sort(doc! { "counter": -1 })
+        .limit(MAX_SUGGESTIONS)
+        .await?
+        .try_collect()
+        .await?;
+
+    Ok(users)
+}
+
+/// Creates the index of the name search and fills the lowercase names of the accounts created before it.
+pub async fn prepare_name_search(db: &Database) -> Result<(), mongodb::error::Error> {
+    let time = Instant::now();
+    let collection: Collection<Document> = db.collection("users");
+
+    // A wildcard index covers the nicknames of every guild
+    collection
+        .create_index(IndexModel::builder().keys(doc! { "search.$**": 1 }).build())
+        .await?;
+
+    let users: Vec<Document> = collection
+        .find(doc! { "search": { "$exists": false } })
+        .projection(doc! { "user_id": 1, "username": 1, "global_name": 1, "nicknames": 1 })
+        .await?
+        .try_collect()
+        .await?;
+
+    // Only runs once, for the accounts older than the search field
+    for user in &users {
+        let mut names = doc! {
+            "username": user.get_str("username").unwrap_or_default(),
+            "global_name": user.get_str("global_name").ok(),
+        };
+        for (guild_id, nickname) in user.get_document("nicknames").into_iter().flatten() {
+            names.insert(format!("nicknames.{}", guild_id), nickname.clone());
+        }
+
+        let search: Document = with_search_names(names)
+            .into_iter()
+            .filter(|(key, _)| key.starts_with("search."))
+            .collect();
+
+        collection
+            .update_one(
+                doc! { "user_id": user.get_str("user_id").unwrap_or_default() },
+                doc! { "$set": search },
+            )
+            .await?;
+    }
+
+    if !users.is_empty() {
+        info!(
+            "Filled the search names of {} users | Time: {:?}",
+            users.len(),
+            time.elapsed()
+        );
+    }
+
+    Ok(())
+}
+
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub async fn make_embed(ctx: Context<'_>, user: Document) -> Result<CreateEmbed, Error> {
+    let collection: Collection<Document> = ctx.data().db.collection("users");
+    let locale = locale(ctx).await?;
+    let numbers = number_format(ctx, locale).await?;
+
+    // As for now, the thumbnail is only live if the user views their own profile.
+    let search_user_name = user.get_str("username")?;
+    let thumbnail = if user.get_str("user_id")? == ctx.author().id.to_string() {
         ctx.author().avatar_url().unwrap_or_default()
     } else {
-        user.get_str("avatar_url").unwrap().to_string()
+        user.get_str("avatar_url").unwrap_or_default().to_string()
+    };
+
+    let counter = user.get_i64("counter")?;
+
+    // The display name is the title, the username is shown under it if it's different
+    let name = display_name(&user, ctx.guild_id());
+    let score = tr!(locale, "profile.score", score = numbers.score(counter));
+    let description = if name == search_user_name {
+        score
+    } else {
+        format!("@{}\n{}", search_user_name, score)
+    };
+    let (place, total) = rank(&collection, counter).await?;
+    let percentile = place as f64 / total.max(1) as f64 * 100.0;
+
+    // Older accounts don't have a creation date, but the ObjectId holds one
+    let created_at = match user.get_datetime("created_at") {
+        Ok(date) => *date,
+        Err(_) => user.get_object_id("_id")?.timestamp(),
+    };
+
+    let longest_session = user.get_i64("longest_session_secs").unwrap_or(0);
+    let best_cpm = match user.get_f64("best_cpm") {
+        Ok(cpm) => format!("{:.1}", cpm),
+        Err(_) => "-".to_string(),
     };
 
-    CreateEmbed::new()
-        .title(format!("__{}'s Profile__", search_user_name))
-        .description(format!(
-            "Current score: **{}**",
-            user.get_i64("counter").unwrap()
-        ))
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "profile.title", name = name))
+        .description(description)
+        .fields(vec![
+            (
+                tr!(locale, "profile.rank"),
+                tr!(
+                    locale,
+                    "profile.rank_value",
+                    place = numbers.number(place as i64),
+                    total = numbers.number(total as i64),
+                    percentile = format!("{:.1}", percentile)
+                ),
+                true,
+            ),
+            (
+                tr!(locale, "profile.playing_since"),
+                format!("<t:{}:D>", created_at.timestamp_millis() / 1000),
+                true,
+            ),
+            (
+                tr!(locale, "profile.sessions"),
+                numbers.number(user.get_i64("sessions").unwrap_or(0)),
+                true,
+            ),
+            (
+                tr!(locale, "profile.total_clicks"),
+                numbers.number(user.get_i64("total_clicks").unwrap_or(0)),
+                true,
+            ),
+            (
+                tr!(locale, "profile.event_quest_points"),
+                numbers.score(user.get_i64("event_quest_points").unwrap_or(0)),
+                true,
+            ),
+            (
+                tr!(locale, "profile.duels"),
+                tr!(
+                    locale,
+                    "profile.duels_value",
+                    wins = numbers.number(user.get_i64("duel_wins").unwrap_or(0)),
+                    losses = numbers.number(user.get_i64("duel_losses").unwrap_or(0))
+                ),
+                true,
+            ),
+            (
+                tr!(locale, "profile.longest_session"),
+                format!("{}m{:02}s", longest_session / 60, longest_session % 60),
+                true,
+            ),
+            (tr!(locale, "profile.best_cpm"), best_cpm, true),
+        ])
         .color(0x5754d0)
-        .thumbnail(thumbnail)
+        .thumbnail(thumbnail);
+
+    Ok(embed)
 }
@@ -0,0 +1,362 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::{Context, Error, utc_day};
+use crate::format::{NumberFormat, number_format};
+use crate::i18n::{DEFAULT_LOCALE, locale, tr};
+use mongodb::bson::{DateTime, Document, doc};
+use mongodb::{Collection, Database};
+use serde::Deserialize;
+use serenity::all::{
+    ButtonStyle, CreateActionRow, CreateButton, CreateEmbedFooter, CreateInteractionResponse,
+    GuildId,
+};
+use serenity::builder::CreateEmbed;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tracing::info;
+
+// How many quests are active at the same time
+const DAILY_QUESTS: usize = 3;
+const WEEKLY_QUESTS: usize = 2;
+
+static POOL: LazyLock<Vec<Quest>> = LazyLock::new(|| {
+    ron::from_str(include_str!("../../data/quests.ron")).expect("data/quests.ron is invalid")
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+enum Period {
+    Daily,
+    Weekly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+enum Objective {
+    Clicks,
+    Servers,
+    SessionMinutes,
+}
+
+#[derive(Debug, Deserialize)]
+struct Quest {
+    id: String,
+    // Description of the quest in each locale
+    description: HashMap<String, String>,
+    period: Period,
+    objective: Objective,
+    goal: i64,
+    reward: i64,
+}
+
+impl Period {
+    fn name(self) -> &'static str {
+        match self {
+            Period::Daily => "daily",
+            Period::Weekly => "weekly",
+        }
+    }
+
+    /// Identifies the current day or week, quests rotate whenever it changes.
+    fn current(self) -> i64 {
+        let day = utc_day(DateTime::now());
+        match self {
+            Period::Daily => day,
+            // The unix epoch is a Thursday, shifting by 3 days makes weeks start on Monday
+            Period::Weekly => (day + 3).div_euclid(7),
+        }
+    }
+
+    /// Time left before the next rotation
+    fn remaining(self) -> Duration {
+        let next_day = match self {
+            Period::Daily => self.current() + 1,
+            Period::Weekly => (self.current() + 1) * 7 - 3,
+        };
+        let millis = next_day * 86_400_000 - DateTime::now().timestamp_millis();
+        Duration::from_millis(millis.max(0) as u64)
+    }
+}
+
+impl Quest {
+    fn description(&self, locale: &str) -> &str {
+        [locale, DEFAULT_LOCALE]
+            .iter()
+            .find_map(|locale| self.description.get(*locale))
+            .map_or(&self.id, String::as_str)
+    }
+
+    fn progress(&self, record: &Document) -> i64 {
+        let progress = match self.objective {
+            Objective::Clicks => record.get_i64("clicks").unwrap_or(0),
+            Objective::Servers => record
+                .get_array("guilds")
+                .map(|guilds| guilds.len() as i64)
+                .unwrap_or(0),
+            Objective::SessionMinutes => record.get_i64("longest_session_secs").unwrap_or(0) / 60,
+        };
+        progress.min(self.goal)
+    }
+}
+
+/// View and claim your daily and weekly quests
+#[poise::command(slash_command)]
+pub async fn quests(ctx: Context<'_>) -> Result<(), Error> {
+    let db = &ctx.data().db;
+    let user_id = ctx.author().id.to_string();
+    let locale = locale(ctx).await?;
+    let numbers = number_format(ctx, locale).await?;
+
+    let records = [
+        fetch_record(db, &user_id, Period::Daily).await?,
+        fetch_record(db, &user_id, Period::Weekly).await?,
+    ];
+
+    let builder = poise::reply::CreateReply::default()
+        .embed(make_embed(locale, numbers, &records))
+        .components(vec![CreateActionRow::Buttons(vec![
+            CreateButton::new("quests_claim")
+                .label(tr!(locale, "quests.claim"))
+                .style(ButtonStyle::Success),
+        ])]);
+
+    let reply = ctx.send(builder).await?;
+    let msg = reply.message().await?;
+
+    let interaction = msg
+        .await_component_interaction(ctx)
+        .author_id(ctx.author().id)
+        .timeout(Duration::from_secs(300))
+        .await;
+
+    let Some(interaction) = interaction else {
+        reply
+            .edit(ctx, poise::reply::CreateReply::default().components(vec![]))
+            .await?;
+        return Ok(());
+    };
+
+    interaction
+        .create_response(ctx, CreateInteractionResponse::Acknowledge)
+        .await?;
+
+    let reward = claim_rewards(db, &user_id).await?;
+
+    let records = [
+        fetch_record(db, &user_id, Period::Daily).await?,
+        fetch_record(db, &user_id, Period::Weekly).await?,
+    ];
+
+    let content = if reward > 0 {
+        info!("{} claimed {} from quests", user_id, reward);
+        tr!(locale, "quests.claimed", reward = numbers.number(reward))
+    } else {
+        tr!(locale, "quests.nothing")
+    };
+
+    reply
+        .edit(
+            ctx,
+            poise::reply::CreateReply::default()
+                .content(content)
+                .embed(make_embed(locale, numbers, &records))
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+fn make_embed(locale: &str, numbers: NumberFormat, records: &[Document; 2]) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .title(tr!(locale, "quests.title"))
+        .color(0x5754d0);
+
+    for (period, record) in [Period::Daily, Period::Weekly].into_iter().zip(records) {
+        let claimed = claimed(record);
+        let lines: Vec<String> = active_quests(period)
+            .iter()
+            .map(|quest| {
+                let progress = quest.progress(record);
+                let status = if claimed.contains(&quest.id) {
+                    "✅"
+                } else if progress >= quest.goal {
+                    "🎁"
+                } else {
+                    "▫️"
+                };
+                tr!(
+                    locale,
+                    "quests.line",
+                    status = status,
+                    description = quest.description(locale),
+                    progress = numbers.number(progress),
+                    goal = numbers.number(quest.goal),
+                    reward = numbers.number(quest.reward)
+                )
+            })
+            .collect();
+
+        let remaining = period.remaining().as_secs();
+        let title = tr!(
+            locale,
+            match period {
+                Period::Daily => "quests.daily",
+                Period::Weekly => "quests.weekly",
+            },
+            hours = remaining / 3600,
+            minutes = format!("{:02}", remaining % 3600 / 60)
+        );
+
+        embed = embed.field(title, lines.join("\n"), false);
+    }
+
+    embed.footer(CreateEmbedFooter::new(tr!(locale, "quests.footer")))
+}
+
+/// Quests of the pool that are active for the current day or week.
+/// The selection only depends on the period, so every user gets the same quests.
+fn active_quests(period: Period) -> Vec<&'static Quest> {
+    let current = period.current();
+    let mut quests: Vec<&Quest> = POOL.iter().filter(|q| q.period == period).collect();
+    quests.sort_by_key(|quest| rotation_key(current, &quest.id));
+    quests.truncate(match period {
+        Period::Daily => DAILY_QUESTS,
+        Period::Weekly => WEEKLY_QUESTS,
+    });
+    quests
+}
+
+// FNV-1a, it's stable across builds unlike std's hasher which would reshuffle quests after an update.
+fn rotation_key(period: i64, id: &str) -> u64 {
+    period
+        .to_le_bytes()
+        .iter()
+        .chain(id.as_bytes())
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+fn claimed(record: &Document) -> Vec<String> {
+    record
+        .get_array("claimed")
+        .map(|claimed| {
+            claimed
+                .iter()
+                .filter_map(|id| id.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+async fn fetch_record(db: &Database, user_id: &str, period: Period) -> Result<Document, Error> {
+    let collection: Collection<Document> = db.collection("quests");
+    let record = collection
+        .find_one(doc! {
+            "user_id": user_id,
+            "period": period.name(),
+            "period_id": period.current()
+        })
+        .await?;
+
+    Ok(record.unwrap_or_default())
+}
+
+/// Gives the reward of every completed quest that hasn't been claimed yet, returns the total.
+async fn claim_rewards(db: &Database, user_id: &str) -> Result<i64, Error> {
+    let quests: Collection<Document> = db.collection("quests");
+    let users: Collection<Document> = db.collection("users");
+    let mut total = 0;
+
+    for period in [Period::Daily, Period::Weekly] {
+        let record = fetch_record(db, user_id, period).await?;
+
+        for quest in active_quests(period) {
+            if quest.progress(&record) < quest.goal {
+                continue;
+            }
+
+            // The filter makes sure a quest can't be claimed twice, even with two /quests open
+            let result = quests
+                .update_one(
+                    doc! {
+                        "user_id": user_id,
+                        "period": period.name(),
+                        "period_id": period.current(),
+                        "claimed": { "$ne": &quest.id }
+                    },
+                    doc! { "$addToSet": { "claimed": &quest.id } },
+                )
+                .await?;
+
+            if result.modified_count == 1 {
+                users
+                    .update_one(
+                        doc! { "user_id": user_id },
+                        doc! { "$inc": { "counter": quest.reward, "event_quest_points": quest.reward } },
+                    )
+                    .await?;
+                total += quest.reward;
+            }
+        }
+    }
+
+    Ok(total)
+}
+
+/// Tracks a click made in a /play session for the current daily and weekly quests.
+pub async fn record_click(
+    db: &Database,
+    user_id: &str,
+    guild_id: Option<GuildId>,
+) -> Result<(), Error> {
+    let mut update = doc! { "$inc": { "clicks": 1i64 } };
+    if let Some(guild_id) = guild_id {
+        update.insert("$addToSet", doc! { "guilds": guild_id.to_string() });
+    }
+
+    update_records(db, user_id, update).await
+}
+
+/// Tracks the length of a finished /play session for the current daily and weekly quests.
+pub async fn record_session(db: &Database, user_id: &str, duration: Duration) -> Result<(), Error> {
+    let update = doc! { "$max": { "longest_session_secs": duration.as_secs() as i64 } };
+
+    update_records(db, user_id, update).await
+}
+
+async fn update_records(db: &Database, user_id: &str, update: Document) -> Result<(), Error> {
+    let collection: Collection<Document> = db.collection("quests");
+
+    for period in [Period::Daily, Period::Weekly] {
+        collection
+            .update_one(
+                doc! {
+                    "user_id": user_id,
+                    "period": period.name(),
+                    "period_id": period.current()
+                },
+                update.clone(),
+            )
+            .upsert(true)
+            .await?;
+    }
+
+    Ok(())
+}
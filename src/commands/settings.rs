@@ -0,0 +1,404 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::play::create_user;
+use crate::commands::{Context, Error};
+use crate::error::BotError;
+use crate::format::NumberFormat;
+use crate::i18n::{locale_for, locales, resolve, tr};
+use mongodb::Collection;
+use mongodb::bson::{Bson, Document, doc};
+use serenity::all::{
+    AutocompleteChoice, ButtonStyle, CreateActionRow, CreateButton, CreateEmbed, ReactionType,
+};
+
+// Value of the language option that goes back to the language of the Discord client
+const AUTO: &str = "auto";
+
+// Play sessions end after this long without a click, unless the user changed it
+pub const DEFAULT_SESSION_TIMEOUT_MINS: i64 = 60;
+pub const DEFAULT_BUTTON_EMOJI: &str = "🔘";
+
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum NumberStyle {
+    #[name = "Full (1,234,567)"]
+    Full,
+    #[name = "Short (1.2M)"]
+    Short,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum Visibility {
+    #[name = "Visible"]
+    Visible,
+    #[name = "Anonymous"]
+    Anonymous,
+    #[name = "Hidden"]
+    Hidden,
+}
+
+impl Visibility {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Visibility::Visible => "visible",
+            Visibility::Anonymous => "anonymous",
+            Visibility::Hidden => "hidden",
+        }
+    }
+
+    fn from_str(value: &str) -> Self {
+        match value {
+            "anonymous" => Visibility::Anonymous,
+            "hidden" => Visibility::Hidden,
+            _ => Visibility::Visible,
+        }
+    }
+}
+
+/// Preferences of a user, stored in the settings subdocument of their record.
+/// Missing fields use the defaults, so users who never ran /settings don't need a document.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub locale: Option<String>,
+    pub short_numbers: bool,
+    pub ephemeral_sessions: bool,
+    pub leaderboard: Visibility,
+    pub session_timeout_mins: i64,
+    pub button_emoji: Option<String>,
+}
+
+impl Settings {
+    pub fn from_document(settings: Option<&Document>) -> Self {
+        let get_bool = |key| settings.and_then(|s| s.get_bool(key).ok()).unwrap_or(false);
+        let get_str = |key| settings.and_then(|s| s.get_str(key).ok()).map(String::from);
+
+        Self {
+            locale: get_str("locale"),
+            short_numbers: get_str("number_format").as_deref() == Some("short"),
+            ephemeral_sessions: get_bool("ephemeral_sessions"),
+            leaderboard: get_str("leaderboard")
+                .as_deref()
+                .map_or(Visibility::Visible, Visibility::from_str),
+            session_timeout_mins: settings
+                .and_then(|s| s.get_i64("session_timeout_mins").ok())
+                .unwrap_or(DEFAULT_SESSION_TIMEOUT_MINS),
+            button_emoji: get_str("button_emoji"),
+        }
+    }
+
+    /// Settings of the author of the command.
+    pub async fn load(ctx: Context<'_>) -> Result<Self, Error> {
+        let collection: Collection<Document> = ctx.data().db.collection("users");
+
+        let user = collection
+            .find_one(doc! { "user_id": ctx.author().id.to_string() })
+            .projection(doc! { "settings": 1 })
+            .await?;
+
+        Ok(Self::from_document(
+            user.as_ref()
+                .and_then(|user| user.get_document("settings").ok()),
+        ))
+    }
+
+    pub fn button_emoji(&self) -> ReactionType {
+        self.button_emoji
+            .as_deref()
+            .and_then(|emoji| ReactionType::try_from(emoji).ok())
+            .unwrap_or_else(|| ReactionType::Unicode(DEFAULT_BUTTON_EMOJI.to_string()))
+    }
+}
+
+/// Change your Button Clicker settings
+#[poise::command(
+    slash_command,
+    subcommands(
+        "view",
+        "language",
+        "numbers",
+        "ephemeral",
+        "leaderboard",
+        "timeout",
+        "emoji"
+    ),
+    subcommand_required
+)]
+pub async fn settings(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// View your current settings
+#[poise::command(slash_command)]
+async fn view(ctx: Context<'_>) -> Result<(), Error> {
+    let settings = Settings::load(ctx).await?;
+    let locale = locale_for(ctx, &settings);
+    let numbers = NumberFormat::new(locale, settings.short_numbers);
+
+    let language = match &settings.locale {
+        Some(_) => locales()
+            .into_iter()
+            .find(|(code, _)| *code == locale)
+            .map_or(locale, |(_, name)| name)
+            .to_string(),
+        None => tr!(locale, "settings.auto"),
+    };
+    let yes_no = |value: bool| tr!(locale, if value { "settings.yes" } else { "settings.no" });
+
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "settings.title"))
+        .fields(vec![
+            (tr!(locale, "settings.language"), language, true),
+            (
+                tr!(locale, "settings.numbers"),
+                numbers.score(1_234_567),
+                true,
+            ),
+            (
+                tr!(locale, "settings.ephemeral"),
+                yes_no(settings.ephemeral_sessions),
+                true,
+            ),
+            (
+                tr!(locale, "settings.leaderboard"),
+                tr!(
+                    locale,
+                    match settings.leaderboard {
+                        Visibility::Visible => "settings.visible",
+                        Visibility::Anonymous => "settings.anonymous",
+                        Visibility::Hidden => "settings.hidden",
+                    }
+                ),
+                true,
+            ),
+            (
+                tr!(locale, "settings.timeout"),
+                tr!(
+                    locale,
+                    "settings.minutes",
+                    minutes = settings.session_timeout_mins
+                ),
+                true,
+            ),
+            (
+                tr!(locale, "settings.emoji"),
+                settings
+                    .button_emoji
+                    .clone()
+                    .unwrap_or_else(|| DEFAULT_BUTTON_EMOJI.to_string()),
+                true,
+            ),
+        ])
+        .color(0x5754d0);
+
+    let builder = poise::reply::CreateReply::default()
+        .embed(embed)
+        .ephemeral(true);
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+/// Choose the language the bot answers you in
+#[poise::command(slash_command)]
+async fn language(
+    ctx: Context<'_>,
+    #[description = "The language to use, \"auto\" follows your Discord language"]
+    #[autocomplete = "autocomplete_language"]
+    language: String,
+) -> Result<(), Error> {
+    let value = if language == AUTO {
+        None
+    } else {
+        let Some(chosen) = resolve(&language) else {
+            let locale = locale_for(ctx, &Settings::load(ctx).await?);
+            return Err(BotError::User(tr!(
+                locale,
+                "settings.unknown_language",
+                language = language
+            ))
+            .into());
+        };
+        Some(chosen)
+    };
+
+    // Answered in the new language
+    let settings = save(ctx, "locale", value).await?;
+    confirm(ctx, &settings, "settings.language_set").await
+}
+
+/// Choose how scores are displayed
+#[poise::command(slash_command)]
+async fn numbers(
+    ctx: Context<'_>,
+    #[description = "Full numbers or short ones with a suffix"] format: NumberStyle,
+) -> Result<(), Error> {
+    let value = match format {
+        NumberStyle::Full => "full",
+        NumberStyle::Short => "short",
+    };
+
+    let settings = save(ctx, "number_format", Some(value)).await?;
+    confirm(ctx, &settings, "settings.numbers_set").await
+}
+
+/// Choose whether your play sessions are only visible to you
+#[poise::command(slash_command)]
+async fn ephemeral(
+    ctx: Context<'_>,
+    #[description = "Only show your play sessions to you"] enabled: bool,
+) -> Result<(), Error> {
+    let settings = save(ctx, "ephemeral_sessions", Some(enabled)).await?;
+    confirm(ctx, &settings, "settings.saved").await
+}
+
+/// Choose how you appear on the leaderboard
+#[poise::command(slash_command)]
+async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "Anonymous and hidden players can't be looked up by name with /profile"]
+    visibility: Visibility,
+) -> Result<(), Error> {
+    let settings = save(ctx, "leaderboard", Some(visibility.as_str())).await?;
+    confirm(ctx, &settings, "settings.saved").await
+}
+
+/// Choose how long a play session lasts without a click
+#[poise::command(slash_command)]
+async fn timeout(
+    ctx: Context<'_>,
+    #[description = "Minutes without a click before the session ends"]
+    #[min = 1]
+    #[max = 60]
+    minutes: i64,
+) -> Result<(), Error> {
+    let settings = save(ctx, "session_timeout_mins", Some(minutes)).await?;
+    confirm(ctx, &settings, "settings.saved").await
+}
+
+/// Choose the emoji of the play button
+#[poise::command(slash_command)]
+async fn emoji(
+    ctx: Context<'_>,
+    #[description = "(Optional) An emoji, leave empty to go back to the default one"] emoji: Option<
+        String,
+    >,
+) -> Result<(), Error> {
+    let emoji = emoji.map(|emoji| emoji.trim().to_string());
+
+    if let Some(emoji) = &emoji
+        && !is_emoji(emoji)
+    {
+        let locale = locale_for(ctx, &Settings::load(ctx).await?);
+        return Err(BotError::User(tr!(locale, "settings.invalid_emoji")).into());
+    }
+
+    let settings = save(ctx, "button_emoji", emoji.as_deref()).await?;
+    let locale = locale_for(ctx, &settings);
+
+    // The preview makes Discord check the emoji, a rejected one is removed so /play keeps working
+    let preview = CreateActionRow::Buttons(vec![
+        CreateButton::new("preview")
+            .emoji(settings.button_emoji())
+            .style(ButtonStyle::Primary)
+            .disabled(true),
+    ]);
+    let builder = poise::reply::CreateReply::default()
+        .content(tr!(locale, "settings.saved"))
+        .components(vec![preview])
+        .ephemeral(true);
+
+    if ctx.send(builder).await.is_err() {
+        save(ctx, "button_emoji", None::<&str>).await?;
+        return Err(BotError::User(tr!(locale, "settings.invalid_emoji")).into());
+    }
+
+    Ok(())
+}
+
+// Either a custom emoji (<:name:id>) or a short unicode sequence without letters or digits
+fn is_emoji(text: &str) -> bool {
+    if text.starts_with('<') {
+        return matches!(
+            ReactionType::try_from(text),
+            Ok(ReactionType::Custom { .. })
+        );
+    }
+
+    !text.is_empty()
+        && text.chars().count() <= 8
+        && !text
+            .chars()
+            .any(|c| c.is_ascii_alphanumeric() || c.is_whitespace())
+}
+
+/// Sets (or unsets with None) a setting of the author, creating their account if needed.
+/// Returns the settings once updated.
+async fn save(
+    ctx: Context<'_>,
+    key: &str,
+    value: Option<impl Into<Bson>>,
+) -> Result<Settings, Error> {
+    let collection: Collection<Document> = ctx.data().db.collection("users");
+    let user_id = ctx.author().id.to_string();
+    let field = format!("settings.{}", key);
+
+    let update = match value {
+        Some(value) => doc! { "$set": { field: value.into() } },
+        None => doc! { "$unset": { field: "" } },
+    };
+
+    if collection
+        .find_one(doc! { "user_id": &user_id })
+        .await?
+        .is_none()
+    {
+        create_user(ctx, &collection).await?;
+    }
+
+    collection
+        .update_one(doc! { "user_id": &user_id }, update)
+        .await?;
+
+    Settings::load(ctx).await
+}
+
+async fn confirm(ctx: Context<'_>, settings: &Settings, key: &str) -> Result<(), Error> {
+    let locale = locale_for(ctx, settings);
+    let numbers = NumberFormat::new(locale, settings.short_numbers);
+
+    let builder = poise::reply::CreateReply::default()
+        .content(tr!(locale, key, example = numbers.score(1_234_567)))
+        .ephemeral(true);
+
+    ctx.send(builder).await?;
+
+    Ok(())
+}
+
+async fn autocomplete_language(_: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let partial = partial.to_lowercase();
+
+    std::iter::once((AUTO, "Auto"))
+        .chain(locales())
+        .filter(|(locale, name)| {
+            locale.starts_with(&partial) || name.to_lowercase().contains(&partial)
+        })
+        .map(|(locale, name)| AutocompleteChoice::new(name, locale))
+        .collect()
+}
@@ -17,7 +17,8 @@
  */
 
 use crate::commands::play::create_user;
-use crate::commands::{Context, Error};
+use crate::commands::{Context, Error, with_search_names};
+use crate::i18n::{locale, tr};
 use mongodb::Collection;
 use mongodb::bson::{Document, doc};
 
@@ -38,23 +39,33 @@ pub async fn sync(ctx: Context<'_>) -> Result<(), Error> {
     }
 
     // Force update all infos
+    let mut infos = doc! {
+        "username": ctx.author().name.clone(),
+        "global_name": ctx.author().global_name.clone(),
+        "user_id": ctx.author().id.to_string(),
+        "avatar_url": ctx.author().avatar_url().unwrap_or_default()
+    };
+    if let Some(guild_id) = ctx.guild_id() {
+        let nickname = ctx
+            .author_member()
+            .await
+            .and_then(|member| member.nick.clone());
+        infos.insert(format!("nicknames.{}", guild_id), nickname);
+    }
+
     collection
         .update_one(
             doc! {
                 "user_id": ctx.author().id.to_string()
             },
             doc! {
-                "$set": {
-                    "username": ctx.author().name.clone(),
-                    "user_id": ctx.author().id.to_string(),
-                    "avatar_url": ctx.author().avatar_url().unwrap_or_default()
-                }
+                "$set": with_search_names(infos)
             },
         )
         .await?;
 
-    let builder = poise::reply::CreateReply::default()
-        .content("Your account has been synchronized and fixed!");
+    let builder =
+        poise::reply::CreateReply::default().content(tr!(locale(ctx).await?, "sync.done"));
 
     ctx.send(builder).await?;
 
@@ -0,0 +1,540 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::handler::update_presence;
+use crate::{CONFIG, StartupError};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use serenity::all::{CacheHttp, ChannelId, Http};
+use serenity::utils::token;
+use std::env;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::{error, info, warn};
+
+// Used when no --config flag and no BUTTON_CLICKER_CONFIG variable is given
+const DEFAULT_CONFIG_PATH: &str = "config.ron";
+
+// Environment variables, they take precedence over the values from the config file
+const ENV_CONFIG_PATH: &str = "BUTTON_CLICKER_CONFIG";
+const ENV_TOKEN: &str = "BUTTON_CLICKER_TOKEN";
+const ENV_MONGODB_URI: &str = "BUTTON_CLICKER_MONGODB_URI";
+const ENV_LOG_CHANNEL_ID: &str = "BUTTON_CLICKER_LOG_CHANNEL_ID";
+const ENV_HTTP_ADDRESS: &str = "BUTTON_CLICKER_HTTP_ADDRESS";
+
+// How often the config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+// Discord cuts activity names longer than this
+const MAX_PRESENCE_LENGTH: usize = 128;
+
+const USAGE: &str = "\
+Usage: button_clicker [OPTIONS]
+
+Options:
+  --config <PATH>           Path to the config file (default: config.ron)
+  --print-default-config    Print a default config file to stdout and exit
+  -h, --help                Print this help and exit
+
+Environment variables (override the config file):
+  BUTTON_CLICKER_CONFIG           Path to the config file, same as --config
+  BUTTON_CLICKER_TOKEN            Discord bot token
+  BUTTON_CLICKER_MONGODB_URI      MongoDB connection string
+  BUTTON_CLICKER_LOG_CHANNEL_ID   Channel ID for logging
+  BUTTON_CLICKER_HTTP_ADDRESS     Address of the metrics and health server, e.g. 0.0.0.0:9100";
+
+// Missing fields fall back to their default so a partial file can be completed by the environment
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    token: String,               // Bot token
+    log_channel_id: Option<u64>, // (optional) Channel ID for logging
+    mongodb_uri: String,
+    presence: Option<String>, // (optional) Activity shown when no event is running
+    http_address: Option<String>, // (optional) Address serving /metrics, /healthz and /readyz, e.g. "0.0.0.0:9100"
+}
+
+/// The part of the config only read on startup, changing it requires a restart.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StaticConfig {
+    pub(crate) token: String,
+    pub(crate) mongodb_uri: String,
+    pub(crate) http_address: Option<String>,
+}
+
+/// The part of the config applied live when the file changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DynamicConfig {
+    pub log_channel_id: Option<u64>,
+    pub presence: Option<String>,
+}
+
+/// What a reload changed.
+#[derive(Debug, Default)]
+pub struct Reload {
+    /// Fields that were applied live
+    pub applied: Vec<&'static str>,
+    /// Fields that changed in the file but only take effect after a restart
+    pub restart_required: Vec<&'static str>,
+}
+
+static DYNAMIC: RwLock<DynamicConfig> = RwLock::new(DynamicConfig {
+    log_channel_id: None,
+    presence: None,
+});
+// Where the config was loaded from on startup, reloads read the same file
+static SOURCE: OnceLock<(PathBuf, bool)> = OnceLock::new();
+
+/// The current value of the live config, read it again every time instead of keeping it around.
+pub fn dynamic() -> DynamicConfig {
+    DYNAMIC.read().unwrap().clone()
+}
+
+/// What the bot was asked to do on the command line.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Start the bot, optionally with a config file given through --config
+    Run {
+        config_path: Option<PathBuf>,
+    },
+    PrintDefaultConfig,
+    Help,
+}
+
+impl Command {
+    /// Parses the command line arguments, without the program name.
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Command, String> {
+        let mut config_path = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--config" => match args.next() {
+                    Some(path) => config_path = Some(PathBuf::from(path)),
+                    None => return Err(String::from("--config requires a path")),
+                },
+                "--print-default-config" => return Ok(Command::PrintDefaultConfig),
+                "-h" | "--help" => return Ok(Command::Help),
+                _ => match arg.strip_prefix("--config=") {
+                    Some(path) => config_path = Some(PathBuf::from(path)),
+                    None => return Err(format!("Unknown argument: {}", arg)),
+                },
+            }
+        }
+        Ok(Command::Run { config_path })
+    }
+
+    pub fn usage() -> &'static str {
+        USAGE
+    }
+}
+
+impl Config {
+    /// The default config file, as written on first start or by --print-default-config.
+    pub fn default_file() -> String {
+        ron::ser::to_string_pretty(&Config::default(), PrettyConfig::default())
+            .expect("the default config is always serializable")
+    }
+
+    /// Loads the config on startup, the dynamic part is stored and the static part returned.
+    pub fn init(config_path: Option<PathBuf>) -> Result<StaticConfig, StartupError> {
+        // A path given explicitly has to exist, only the default location is optional
+        let (path, explicit) =
+            match config_path.or_else(|| env::var_os(ENV_CONFIG_PATH).map(PathBuf::from)) {
+                Some(path) => (path, true),
+                None => (PathBuf::from(DEFAULT_CONFIG_PATH), false),
+            };
+
+        let (static_config, dynamic) = Config::load(&path, explicit, true)?.split();
+        *DYNAMIC.write().unwrap() = dynamic;
+        let _ = SOURCE.set((path, explicit));
+        Ok(static_config)
+    }
+
+    fn split(self) -> (StaticConfig, DynamicConfig) {
+        (
+            StaticConfig {
+                token: self.token,
+                mongodb_uri: self.mongodb_uri,
+                http_address: self.http_address,
+            },
+            DynamicConfig {
+                log_channel_id: self.log_channel_id,
+                presence: self.presence,
+            },
+        )
+    }
+
+    /// Loads the config in layers: defaults, then the config file, then the environment.
+    /// The default file is only written on startup, a reload never creates files.
+    fn load(path: &Path, explicit: bool, create_default: bool) -> Result<Config, StartupError> {
+        // The RON file is better in this case because it's one of the few serde impl that actually supports
+        // the u64 format without having to do parsing and conversion and stuff.
+        // + it's "native" to Rust's type of syntax.
+        let mut config = match fs::read_to_string(path) {
+            Ok(config) => {
+                let config = ron::from_str(&config).map_err(StartupError::ConfigParse)?;
+                info!("Loaded config from {}", path.display());
+                config
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !explicit => {
+                // Running only from the environment (e.g. in a container) doesn't need a file
+                if env::var_os(ENV_TOKEN).is_some() || !create_default {
+                    info!("No config file found, using the environment");
+                    Config::default()
+                } else {
+                    error!("Config file not found. Creating a default config file.");
+                    fs::write(path, Config::default_file()).map_err(StartupError::ConfigWrite)?;
+                    return Err(StartupError::ConfigCreated);
+                }
+            }
+            Err(e) => return Err(StartupError::ConfigRead(path.to_path_buf(), e)),
+        };
+
+        // Report everything at once instead of making the user fix one value per restart
+        let mut problems = config.apply_env();
+        problems.extend(config.problems());
+        if !problems.is_empty() {
+            return Err(StartupError::ConfigInvalid(problems));
+        }
+        Ok(config)
+    }
+
+    // Only checks the shape of the values, the log channel can't be checked before login
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.token.is_empty() {
+            problems.push(format!(
+                "token is empty, set it in the file or with {}",
+                ENV_TOKEN
+            ));
+        } else if token::validate(&self.token).is_err() {
+            problems.push(String::from(
+                "token doesn't look like a Discord bot token, it should have 3 parts separated by dots",
+            ));
+        }
+
+        if self.mongodb_uri.is_empty() {
+            problems.push(format!(
+                "mongodb_uri is empty, set it in the file or with {}",
+                ENV_MONGODB_URI
+            ));
+        } else if !self.mongodb_uri.starts_with("mongodb://")
+            && !self.mongodb_uri.starts_with("mongodb+srv://")
+        {
+            problems.push(String::from(
+                "mongodb_uri must start with mongodb:// or mongodb+srv://",
+            ));
+        }
+
+        if self.log_channel_id == Some(0) {
+            problems.push(String::from(
+                "log_channel_id can't be 0, use None to disable logging",
+            ));
+        }
+
+        if let Some(address) = &self.http_address
+            && address.parse::<SocketAddr>().is_err()
+        {
+            problems.push(format!(
+                "http_address must be an IP address with a port like \"0.0.0.0:9100\", got {:?}",
+                address
+            ));
+        }
+
+        if self.presence.as_ref().is_some_and(|presence| {
+            presence.trim().is_empty() || presence.chars().count() > MAX_PRESENCE_LENGTH
+        }) {
+            problems.push(format!(
+                "presence must be between 1 and {} characters, use None for the default",
+                MAX_PRESENCE_LENGTH
+            ));
+        }
+
+        problems
+    }
+
+    // Returns the problems instead of stopping at the first one, they're reported with the others
+    fn apply_env(&mut self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if let Ok(token) = env::var(ENV_TOKEN) {
+            self.token = token;
+        }
+        if let Ok(uri) = env::var(ENV_MONGODB_URI) {
+            self.mongodb_uri = uri;
+        }
+        if let Ok(channel) = env::var(ENV_LOG_CHANNEL_ID) {
+            // An empty value disables logging, which a file can't be overridden with otherwise
+            if channel.is_empty() {
+                self.log_channel_id = None;
+            } else {
+                match channel.parse() {
+                    Ok(channel) => self.log_channel_id = Some(channel),
+                    Err(_) => problems.push(format!(
+                        "{} must be a channel ID, got {:?}",
+                        ENV_LOG_CHANNEL_ID, channel
+                    )),
+                }
+            }
+        }
+        if let Ok(address) = env::var(ENV_HTTP_ADDRESS) {
+            // Empty disables the server, same as for the log channel
+            self.http_address = Some(address).filter(|address| !address.is_empty());
+        }
+        problems
+    }
+}
+
+/// Checks that the log channel exists and the bot can see it, which needs to be logged in.
+pub async fn check_log_channel(
+    cache_http: impl CacheHttp,
+    channel: u64,
+) -> Result<(), StartupError> {
+    match ChannelId::new(channel).to_channel(cache_http).await {
+        Ok(found) if found.clone().guild().is_some() => {
+            info!("Log channel {} is reachable", channel);
+            Ok(())
+        }
+        Ok(_) => Err(StartupError::LogChannel(
+            channel,
+            String::from("it isn't a server channel"),
+        )),
+        Err(e) => Err(StartupError::LogChannel(channel, e.to_string())),
+    }
+}
+
+/// Reads the config file again and applies its dynamic part.
+/// The current config is kept as is if the new one is invalid.
+pub async fn reload(cache_http: impl CacheHttp) -> Result<Reload, StartupError> {
+    let (path, explicit) = SOURCE.get().expect("the config is loaded on startup");
+    let (static_config, new) = Config::load(path, *explicit, false)?.split();
+    let current = dynamic();
+
+    let mut reload = Reload::default();
+    if new.log_channel_id != current.log_channel_id {
+        if let Some(channel) = new.log_channel_id {
+            check_log_channel(cache_http, channel).await?;
+        }
+        reload.applied.push("log_channel_id");
+    }
+    if new.presence != current.presence {
+        reload.applied.push("presence");
+    }
+
+    if let Some(running) = CONFIG.get() {
+        if static_config.token != running.token {
+            reload.restart_required.push("token");
+        }
+        if static_config.mongodb_uri != running.mongodb_uri {
+            reload.restart_required.push("mongodb_uri");
+        }
+        if static_config.http_address != running.http_address {
+            reload.restart_required.push("http_address");
+        }
+    }
+
+    *DYNAMIC.write().unwrap() = new;
+    if reload.applied.contains(&"presence") {
+        update_presence().await;
+    }
+
+    if !reload.applied.is_empty() {
+        info!("Config reloaded, applied: {}", reload.applied.join(", "));
+    }
+    if !reload.restart_required.is_empty() {
+        warn!(
+            "Config changes need a restart to apply: {}",
+            reload.restart_required.join(", ")
+        );
+    }
+    Ok(reload)
+}
+
+/// Reloads the config whenever the file is modified.
+pub async fn watch(http: Arc<Http>) {
+    let Some((path, _)) = SOURCE.get() else {
+        return;
+    };
+    let mut last_modified = modified(path);
+
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+
+        let modified = modified(path);
+        if modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        if let Err(e) = reload(&http).await {
+            warn!("Config reload failed, keeping the current config: {}", e);
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Config, MAX_PRESENCE_LENGTH};
+    use std::path::PathBuf;
+
+    // Changes a valid config so it has exactly one problem
+    type Breakage = fn(&mut Config);
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn valid() -> Config {
+        Config {
+            token: String::from("MTIz.NDU2.Nzg5"),
+            log_channel_id: Some(1234),
+            mongodb_uri: String::from("mongodb://localhost:27017"),
+            presence: Some(String::from("Clicking")),
+            http_address: Some(String::from("0.0.0.0:9100")),
+        }
+    }
+
+    #[test]
+    fn parse_commands() {
+        let run = |path: Option<&str>| Command::Run {
+            config_path: path.map(PathBuf::from),
+        };
+        let cases: [(&[&str], Command); 8] = [
+            (&[], run(None)),
+            (&["--config", "bot.ron"], run(Some("bot.ron"))),
+            (&["--config=bot.ron"], run(Some("bot.ron"))),
+            (&["--config", "a.ron", "--config=b.ron"], run(Some("b.ron"))),
+            (&["--print-default-config"], Command::PrintDefaultConfig),
+            (&["-h"], Command::Help),
+            (&["--help"], Command::Help),
+            (&["--config", "bot.ron", "--help"], Command::Help),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(parse(args), Ok(expected), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn parse_errors() {
+        let cases: [(&[&str], &str); 5] = [
+            (&["--config"], "--config requires a path"),
+            (&["--conf"], "Unknown argument: --conf"),
+            (&["-c", "bot.ron"], "Unknown argument: -c"),
+            (&["bot.ron"], "Unknown argument: bot.ron"),
+            (&["--help=yes"], "Unknown argument: --help=yes"),
+        ];
+
+        for (args, expected) in cases {
+            assert_eq!(parse(args), Err(String::from(expected)), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn valid_config_has_no_problems() {
+        assert_eq!(valid().problems(), Vec::<String>::new());
+
+        let optional = Config {
+            log_channel_id: None,
+            presence: None,
+            http_address: None,
+            ..valid()
+        };
+        assert_eq!(optional.problems(), Vec::<String>::new());
+
+        let srv = Config {
+            mongodb_uri: String::from("mongodb+srv://cluster.example.com"),
+            token: String::from("Bot MTIz.NDU2.Nzg5"),
+            ..valid()
+        };
+        assert_eq!(srv.problems(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn each_problem_is_reported() {
+        let cases: [(Breakage, &str); 11] = [
+            (|c| c.token.clear(), "token is empty"),
+            (
+                |c| c.token = String::from("MTIz.NDU2"),
+                "token doesn't look like",
+            ),
+            (
+                |c| c.token = String::from("a.b.c.d"),
+                "token doesn't look like",
+            ),
+            (|c| c.mongodb_uri.clear(), "mongodb_uri is empty"),
+            (
+                |c| c.mongodb_uri = String::from("localhost:27017"),
+                "mongodb_uri must start with",
+            ),
+            (|c| c.log_channel_id = Some(0), "log_channel_id can't be 0"),
+            (
+                |c| c.http_address = Some(String::from("9100")),
+                "http_address must be",
+            ),
+            (
+                |c| c.http_address = Some(String::from("localhost:9100")),
+                "http_address must be",
+            ),
+            (|c| c.presence = Some(String::new()), "presence must be"),
+            (
+                |c| c.presence = Some(String::from("   ")),
+                "presence must be",
+            ),
+            (
+                |c| c.presence = Some("a".repeat(MAX_PRESENCE_LENGTH + 1)),
+                "presence must be",
+            ),
+        ];
+
+        for (change, expected) in cases {
+            let mut config = valid();
+            change(&mut config);
+            let problems = config.problems();
+            assert_eq!(problems.len(), 1, "{:?}", problems);
+            assert!(problems[0].starts_with(expected), "{:?}", problems);
+        }
+    }
+
+    #[test]
+    fn problems_are_all_reported_at_once() {
+        let config = Config {
+            log_channel_id: Some(0),
+            http_address: Some(String::from("nowhere")),
+            ..Config::default()
+        };
+
+        assert_eq!(config.problems().len(), 4);
+    }
+
+    #[test]
+    fn default_file_can_be_read_back() {
+        let config: Config = ron::from_str(&Config::default_file()).unwrap();
+        assert_eq!(config.token, "");
+        assert_eq!(config.log_channel_id, None);
+    }
+}
@@ -0,0 +1,188 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::{Context, Data, Error};
+use crate::i18n::{DEFAULT_LOCALE, locale, tr};
+use crate::{StartupError, config};
+use serenity::all::{ChannelId, CreateEmbed, CreateMessage};
+use std::fmt;
+use tracing::{error, warn};
+
+// Longest error text sent to the log channel, embed fields are limited to 1024 characters
+const MAX_LOGGED_ERROR: usize = 900;
+
+/// What went wrong in a command, which decides what the user is told.
+#[derive(Debug)]
+pub enum BotError {
+    /// A mistake of the user, the message is already translated and shown as is
+    User(String),
+    Database(mongodb::error::Error),
+    Discord(serenity::Error),
+    Internal(Error),
+}
+
+impl BotError {
+    /// Finds out the kind of an error returned by a command.
+    /// Errors that aren't a BotError are sorted by their type, anything unknown being internal.
+    pub fn classify(error: Error) -> Self {
+        let error = match error.downcast::<BotError>() {
+            Ok(error) => return *error,
+            Err(error) => error,
+        };
+        let error = match error.downcast::<mongodb::error::Error>() {
+            Ok(error) => return BotError::Database(*error),
+            Err(error) => error,
+        };
+        match error.downcast::<serenity::Error>() {
+            Ok(error) => BotError::Discord(*error),
+            Err(error) => BotError::Internal(error),
+        }
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            BotError::User(_) => "user",
+            BotError::Database(_) => "database",
+            BotError::Discord(_) => "discord",
+            BotError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl fmt::Display for BotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BotError::User(message) => f.write_str(message),
+            BotError::Database(error) => write!(f, "Database error: {}", error),
+            BotError::Discord(error) => write!(f, "Discord error: {}", error),
+            BotError::Internal(error) => write!(f, "Internal error: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for BotError {}
+
+/// Error handler of the framework, set in the FrameworkOptions.
+pub async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
+    match error {
+        poise::FrameworkError::Command { error, ctx, .. } => {
+            report(ctx, BotError::classify(error)).await;
+        }
+        // The bot can't work without a finished setup, stop with the matching exit code
+        poise::FrameworkError::Setup { error, .. } => {
+            let error = match error.downcast::<StartupError>() {
+                Ok(error) => *error,
+                Err(error) => match error.downcast::<serenity::Error>() {
+                    Ok(error) => StartupError::from(*error),
+                    Err(error) => {
+                        error!("Setup failed: {}", error);
+                        std::process::exit(1);
+                    }
+                },
+            };
+            error!("{}", error);
+            std::process::exit(error.exit_code());
+        }
+        // Argument parsing, owner checks and such are already handled well by poise
+        error => {
+            if let Err(e) = poise::builtins::on_error(error).await {
+                warn!("Failed to handle an error: {}", e);
+            }
+        }
+    }
+}
+
+/// Tells the user what went wrong, and the operator if it isn't the user's fault.
+async fn report(ctx: Context<'_>, error: BotError) {
+    // The database may be the reason of the error, the locale then falls back to the default one
+    let locale = locale(ctx).await.unwrap_or(DEFAULT_LOCALE);
+
+    let description = match &error {
+        BotError::User(message) => message.clone(),
+        BotError::Database(_) => tr!(locale, "error.database"),
+        BotError::Discord(_) => tr!(locale, "error.discord"),
+        BotError::Internal(_) => tr!(locale, "error.internal"),
+    };
+
+    if !matches!(error, BotError::User(_)) {
+        error!(
+            "Command /{} failed for {}: {:?}",
+            ctx.command().qualified_name,
+            ctx.author().id,
+            error
+        );
+        if let Err(e) = log_error(ctx, &error).await {
+            warn!("Failed to report an error to the log channel: {}", e);
+        }
+    }
+
+    let embed = CreateEmbed::new()
+        .title(tr!(locale, "error.title"))
+        .description(description)
+        .color(0x5754d0);
+
+    let builder = poise::reply::CreateReply::default()
+        .embed(embed)
+        .ephemeral(true);
+
+    if let Err(e) = ctx.send(builder).await {
+        warn!("Failed to send an error message: {}", e);
+    }
+}
+
+async fn log_error(ctx: Context<'_>, error: &BotError) -> Result<(), Error> {
+    let Some(channel) = config::dynamic().log_channel_id else {
+        return Ok(());
+    };
+
+    let mut details = format!("{:?}", error);
+    if details.len() > MAX_LOGGED_ERROR {
+        let mut end = MAX_LOGGED_ERROR;
+        while !details.is_char_boundary(end) {
+            end -= 1;
+        }
+        details.truncate(end);
+        details.push('…');
+    }
+
+    let embed = CreateEmbed::new()
+        .title("__Command error__")
+        .fields(vec![
+            (
+                "__Command__",
+                format!("/{}", ctx.command().qualified_name),
+                true,
+            ),
+            ("__User__", ctx.author().id.to_string(), true),
+            (
+                "__Guild__",
+                ctx.guild_id()
+                    .map_or("DM".to_string(), |guild_id| guild_id.to_string()),
+                true,
+            ),
+            ("__Kind__", error.kind().to_string(), true),
+            ("__Error__", format!("```\n{}\n```", details), false),
+        ])
+        .color(0x5754d0);
+
+    ChannelId::new(channel)
+        .send_message(ctx, CreateMessage::new().embed(embed))
+        .await?;
+
+    Ok(())
+}
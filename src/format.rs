@@ -0,0 +1,174 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::settings::Settings;
+use crate::commands::{Context, Error};
+use crate::i18n::translate;
+use readable::num::Int;
+
+// Suffixes of the short format, from the biggest unit to the smallest
+const UNITS: [(i64, &str); 4] = [
+    (1_000_000_000_000, "number.trillion"),
+    (1_000_000_000, "number.billion"),
+    (1_000_000, "number.million"),
+    (1_000, "number.thousand"),
+];
+
+/// How numbers are shown to a user: separators come from their locale,
+/// and scores can be shortened (1.2K) if they chose so in /settings.
+#[derive(Debug, Clone, Copy)]
+pub struct NumberFormat {
+    locale: &'static str,
+    short: bool,
+}
+
+impl NumberFormat {
+    pub fn new(locale: &'static str, short: bool) -> Self {
+        Self { locale, short }
+    }
+
+    /// Always the full number with thousands separators, used for counts and exact amounts.
+    pub fn number(self, number: i64) -> String {
+        // readable always groups with a comma, it's swapped for the separator of the locale
+        Int::from(number)
+            .to_string()
+            .replace(',', &translate(self.locale, "number.group_separator", &[]))
+    }
+
+    /// A score, shortened if the user prefers it.
+    pub fn score(self, score: i64) -> String {
+        if !self.short {
+            return self.number(score);
+        }
+
+        let Some((unit, key)) = UNITS
+            .into_iter()
+            .find(|(unit, _)| score.unsigned_abs() >= *unit as u64)
+        else {
+            return self.number(score);
+        };
+
+        // Truncated toward zero to one decimal so a score is never shown bigger than it is
+        let tenths = score / (unit / 10);
+        let value = if tenths % 10 == 0 {
+            self.number(tenths / 10)
+        } else {
+            format!(
+                "{}{}{}",
+                self.number(tenths / 10),
+                translate(self.locale, "number.decimal_separator", &[]),
+                (tenths % 10).abs()
+            )
+        };
+
+        translate(self.locale, key, &[("value", value)])
+    }
+}
+
+/// Number format of the author of the command, `locale` being the one returned by `i18n::locale`.
+pub async fn number_format(ctx: Context<'_>, locale: &'static str) -> Result<NumberFormat, Error> {
+    let settings = Settings::load(ctx).await?;
+
+    Ok(NumberFormat::new(locale, settings.short_numbers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NumberFormat;
+
+    #[test]
+    fn number_uses_the_separator_of_the_locale() {
+        let cases = [
+            ("en", 0, "0"),
+            ("en", 999, "999"),
+            ("en", 1_234_567, "1,234,567"),
+            ("en", -1_234_567, "-1,234,567"),
+            ("fr", 1_234_567, "1\u{202F}234\u{202F}567"),
+            ("fr", -1_000, "-1\u{202F}000"),
+        ];
+
+        for (locale, number, expected) in cases {
+            assert_eq!(
+                NumberFormat::new(locale, false).number(number),
+                expected,
+                "{} in {}",
+                number,
+                locale
+            );
+        }
+    }
+
+    #[test]
+    fn score_is_only_shortened_when_asked() {
+        assert_eq!(NumberFormat::new("en", false).score(1_500), "1,500");
+        assert_eq!(NumberFormat::new("en", true).score(1_500), "1.5K");
+    }
+
+    #[test]
+    fn short_score_is_truncated() {
+        let cases = [
+            (0, "0"),
+            (999, "999"),
+            (1_000, "1K"),
+            (1_099, "1K"),
+            (1_999, "1.9K"),
+            (999_999, "999.9K"),
+            (1_000_000, "1M"),
+            (2_550_000_000, "2.5B"),
+            (7_000_000_000_000, "7T"),
+            (i64::MAX, "9,223,372T"),
+        ];
+
+        for (score, expected) in cases {
+            assert_eq!(
+                NumberFormat::new("en", true).score(score),
+                expected,
+                "{}",
+                score
+            );
+        }
+    }
+
+    #[test]
+    fn short_negative_score_keeps_its_sign() {
+        let cases = [
+            (-999, "-999"),
+            (-1_000, "-1K"),
+            (-1_099, "-1K"),
+            (-1_999, "-1.9K"),
+            (-2_500_000, "-2.5M"),
+            (i64::MIN, "-9,223,372T"),
+        ];
+
+        for (score, expected) in cases {
+            assert_eq!(
+                NumberFormat::new("en", true).score(score),
+                expected,
+                "{}",
+                score
+            );
+        }
+    }
+
+    #[test]
+    fn short_score_uses_the_units_of_the_locale() {
+        let numbers = NumberFormat::new("fr", true);
+        assert_eq!(numbers.score(1_500), "1,5\u{202F}k");
+        assert_eq!(numbers.score(-3_000_000_000), "-3\u{202F}Md");
+    }
+}
@@ -16,19 +16,35 @@
  *  as defined by the AGPLv3 license.
  */
 
-use crate::{CONFIG, Handler};
+use crate::metrics::observe_event;
+use crate::{Handler, config};
 use serenity::all::{
-    ChannelId, CreateEmbed, CreateEmbedFooter, CreateMessage, Guild, UnavailableGuild,
+    ChannelId, CreateEmbed, CreateEmbedFooter, CreateMessage, Guild, UnavailableGuild, User,
 };
 use serenity::prelude::*;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+
+// The owner is only informative, so a failed lookup falls back to their ID instead of
+// dropping the whole log message.
+async fn owner_label(ctx: &Context, guild: &Guild, label: fn(User) -> String) -> String {
+    match guild.owner_id.to_user(ctx).await {
+        Ok(user) => label(user),
+        Err(e) => {
+            warn!("Failed to fetch owner of guild {}: {}", guild.id, e);
+            guild.owner_id.to_string()
+        }
+    }
+}
 
 impl Handler {
     pub async fn guild_create(&self, ctx: Context, guild: Guild, is_new: Option<bool>) {
         let instant = std::time::Instant::now();
 
-        if is_new.is_some() && CONFIG.get().unwrap().log_channel_id.is_some() {
-            if !is_new.unwrap() {
+        // Read on every event, the log channel can change with a config reload
+        if let Some(is_new) = is_new
+            && let Some(channel) = config::dynamic().log_channel_id
+        {
+            if !is_new {
                 return;
             }
 
@@ -43,17 +59,14 @@ impl Handler {
                 .icon_url()
                 .unwrap_or_else(|| String::from("https://cdn.discordapp.com/embed/avatars/0.png"));
 
+            let owner = owner_label(&ctx, &guild, |user| user.tag()).await;
             let embed = CreateEmbed::new()
                 .description("**Joined a new guild**")
                 .fields(vec![
                     ("__Name__", guild.name, true),
                     ("__ID__", guild.id.to_string(), true),
                     ("__Members__", guild.member_count.to_string(), true),
-                    (
-                        "__Owner__",
-                        guild.owner_id.to_user(&ctx).await.unwrap().tag(),
-                        true,
-                    ),
+                    ("__Owner__", owner, true),
                 ])
                 .thumbnail(thumbnail_link)
                 .footer(CreateEmbedFooter::new(format!(
@@ -63,11 +76,14 @@ impl Handler {
 
             let builder = CreateMessage::new().embed(embed);
 
-            let channel = ChannelId::from(CONFIG.get().unwrap().log_channel_id.unwrap());
-            let message = channel.send_message(&ctx.http, builder).await;
-            debug!("Sent message: {:?}", message);
+            let channel = ChannelId::from(channel);
+            match channel.send_message(&ctx.http, builder).await {
+                Ok(message) => debug!("Sent message: {:?}", message),
+                Err(e) => warn!("Failed to send guild log message: {}", e),
+            }
         }
 
+        observe_event("guild_create", instant.elapsed());
         info!("Event guild_create | Time: {:?}", instant.elapsed());
     }
 
@@ -88,9 +104,9 @@ impl Handler {
 
         if let Some(guild) = full {
             // If the log channel is not set, we don't want to send a message.
-            if CONFIG.get().unwrap().log_channel_id.is_none() {
+            let Some(channel) = config::dynamic().log_channel_id else {
                 return;
-            }
+            };
 
             info!(
                 "Guild {} left || total {}",
@@ -104,17 +120,14 @@ impl Handler {
                 .icon_url()
                 .unwrap_or_else(|| String::from("https://cdn.discordapp.com/embed/avatars/0.png"));
 
+            let owner = owner_label(&ctx, &guild, |user| user.name).await;
             let embed = CreateEmbed::new()
                 .description("**Left a guild**")
                 .fields(vec![
                     ("__Name__", guild.name, true),
                     ("__ID__", guild.id.to_string(), true),
                     ("__Members__", guild.member_count.to_string(), true),
-                    (
-                        "__Owner__",
-                        guild.owner_id.to_user(&ctx).await.unwrap().name,
-                        true,
-                    ),
+                    ("__Owner__", owner, true),
                 ])
                 .thumbnail(thumbnail_link)
                 .footer(CreateEmbedFooter::new(format!(
@@ -124,11 +137,14 @@ impl Handler {
 
             let builder = CreateMessage::new().embed(embed);
 
-            let channel = ChannelId::from(CONFIG.get().unwrap().log_channel_id.unwrap());
-            let message = channel.send_message(&ctx.http, builder).await;
-            debug!("Sent message: {:?}", message);
+            let channel = ChannelId::from(channel);
+            match channel.send_message(&ctx.http, builder).await {
+                Ok(message) => debug!("Sent message: {:?}", message),
+                Err(e) => warn!("Failed to send guild log message: {}", e),
+            }
         }
 
+        observe_event("guild_delete", instant.elapsed());
         info!("Event guild_delete | Time: {:?}", instant.elapsed());
     }
 }
@@ -0,0 +1,191 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::Handler;
+use crate::commands::Error;
+use crate::commands::with_search_names;
+use mongodb::Database;
+use mongodb::bson::{Document, doc};
+use serenity::all::{GuildMemberUpdateEvent, Member, UserId};
+use serenity::prelude::*;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+// How often the queued profile changes are written to the database
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+// Latest known profile of the users who changed, a user updating several times between two flushes
+// (or seen in several guilds) only gets written once
+static PENDING: Mutex<BTreeMap<UserId, Document>> = Mutex::new(BTreeMap::new());
+
+impl Handler {
+    pub async fn guild_member_update(
+        &self,
+        _ctx: Context,
+        old: Option<Member>,
+        _new: Option<Member>,
+        event: GuildMemberUpdateEvent,
+    ) {
+        let user = &event.user;
+
+        // Role changes also trigger this event, they're skipped when the cache can tell
+        if let Some(old) = old
+            && old.user.name == user.name
+            && old.user.global_name == user.global_name
+            && old.user.avatar == user.avatar
+            && old.nick == event.nick
+        {
+            return;
+        }
+
+        debug!("Queued a profile update for {}", user.id);
+
+        // Nicknames of the same user in other guilds may already be queued, they're kept
+        PENDING
+            .lock()
+            .unwrap()
+            .entry(user.id)
+            .or_default()
+            .extend(with_search_names(doc! {
+                "username": &user.name,
+                "global_name": user.global_name.as_deref(),
+                "avatar_url": user.avatar_url().unwrap_or_default(),
+                format!("nicknames.{}", event.guild_id): event.nick.as_deref(),
+            }));
+    }
+}
+
+/// Background task writing the queued profile changes.
+pub async fn run_profile_sync(db: Database) {
+    loop {
+        tokio::time::sleep(FLUSH_INTERVAL).await;
+
+        if let Err(e) = flush_profiles(&db).await {
+            warn!("Failed to sync profiles: {}", e);
+        }
+    }
+}
+
+/// Writes the queued profile changes in a single command, only registered users are updated.
+pub async fn flush_profiles(db: &Database) -> Result<(), Error> {
+    let pending: Vec<(UserId, Document)> = std::mem::take(&mut *PENDING.lock().unwrap())
+        .into_iter()
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let time = Instant::now();
+
+    // The update command takes many statements at once, unlike the bulk write API it works before MongoDB 8.0
+    let updates: Vec<Document> = pending
+        .iter()
+        .map(|(user_id, profile)| {
+            doc! {
+                "q": { "user_id": user_id.to_string() },
+                "u": { "$set": profile }
+            }
+        })
+        .collect();
+    let result = db
+        .run_command(doc! { "update": "users", "updates": updates, "ordered": false })
+        .await;
+
+    let response = match result {
+        Ok(response) => response,
+        Err(e) => {
+            requeue(pending);
+            return Err(e.into());
+        }
+    };
+
+    // With an unordered update the other statements are still applied when some fail
+    let failed: Vec<usize> = response
+        .get_array("writeErrors")
+        .map(|errors| {
+            errors
+                .iter()
+                .filter_map(|error| error.as_document()?.get_i32("index").ok())
+                .map(|index| index as usize)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    info!(
+        "Synced {} profiles out of {} updates | Time: {:?}",
+        response.get_i32("nModified").unwrap_or(0),
+        pending.len(),
+        time.elapsed()
+    );
+
+    if failed.is_empty() {
+        return Ok(());
+    }
+
+    let count = failed.len();
+    requeue(
+        pending
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| failed.contains(index))
+            .map(|(_, update)| update)
+            .collect(),
+    );
+    Err(format!("{} profile update(s) failed", count).into())
+}
+
+// Queued again for the next flush. A newer update that came in meanwhile is merged on top,
+// so the fields only the failed one carried (e.g. a nickname in another guild) aren't lost.
+fn requeue(failed: Vec<(UserId, Document)>) {
+    let mut pending = PENDING.lock().unwrap();
+    for (user_id, mut profile) in failed {
+        if let Some(newer) = pending.remove(&user_id) {
+            profile.extend(newer);
+        }
+        pending.insert(user_id, profile);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PENDING, requeue};
+    use mongodb::bson::doc;
+    use serenity::all::UserId;
+
+    #[test]
+    fn requeued_update_is_merged_under_the_newer_one() {
+        let user_id = UserId::new(1);
+        PENDING.lock().unwrap().insert(
+            user_id,
+            doc! { "username": "newer", "nicknames.2": "second" },
+        );
+
+        requeue(vec![(
+            user_id,
+            doc! { "username": "older", "nicknames.1": "first" },
+        )]);
+
+        let merged = PENDING.lock().unwrap().remove(&user_id).unwrap();
+        assert_eq!(
+            merged,
+            doc! { "username": "newer", "nicknames.1": "first", "nicknames.2": "second" }
+        );
+    }
+}
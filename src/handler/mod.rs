@@ -17,13 +17,20 @@
  */
 
 use crate::Handler;
-use serenity::all::{Context, EventHandler, Guild, Interaction, Ready, UnavailableGuild};
+use serenity::all::{
+    Context, EventHandler, Guild, GuildMemberUpdateEvent, Interaction, Member, Ready,
+    UnavailableGuild,
+};
 use serenity::async_trait;
 
 mod guild_event;
 mod interaction;
+mod member_update;
 mod ready;
 
+pub(crate) use member_update::flush_profiles;
+pub(crate) use ready::{ready_shards, update_presence};
+
 #[async_trait]
 impl EventHandler for Handler {
     async fn guild_create(&self, ctx: Context, guild: Guild, is_new: Option<bool>) {
@@ -34,6 +41,16 @@ impl EventHandler for Handler {
         self.guild_delete(ctx, incomplete, full).await;
     }
 
+    async fn guild_member_update(
+        &self,
+        ctx: Context,
+        old: Option<Member>,
+        new: Option<Member>,
+        event: GuildMemberUpdateEvent,
+    ) {
+        self.guild_member_update(ctx, old, new, event).await;
+    }
+
     async fn ready(&self, ctx: Context, ready: Ready) {
         self.ready(ctx, ready).await;
     }
@@ -16,10 +16,33 @@
  *  as defined by the AGPLv3 license.
  */
 
-use crate::Handler;
-use serenity::all::{ActivityData, OnlineStatus, Ready};
+use crate::commands::event::{Event, active_event};
+use crate::commands::history::run_snapshots;
+use crate::commands::lottery::run_draws;
+use crate::handler::member_update::run_profile_sync;
+use crate::i18n::DEFAULT_LOCALE;
+use crate::metrics::observe_event;
+use crate::{DATABASE, Handler, config};
+use serenity::all::{ActivityData, OnlineStatus, Ready, ShardId};
 use serenity::prelude::*;
-use tracing::info;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+use tracing::{info, warn};
+
+// How often the presence is refreshed to advertise (or stop advertising) a bonus event
+const PRESENCE_REFRESH: Duration = Duration::from_secs(60);
+
+// Latest context of each shard, a shard gets a new context when it reconnects
+static SHARDS: Mutex<BTreeMap<ShardId, Context>> = Mutex::new(BTreeMap::new());
+// Number of shards the bot runs with, as told by Discord in the ready events
+static SHARD_TOTAL: AtomicU32 = AtomicU32::new(0);
+static PRESENCE_TASK: Once = Once::new();
+static LOTTERY_TASK: Once = Once::new();
+static HISTORY_TASK: Once = Once::new();
+static PROFILE_SYNC_TASK: Once = Once::new();
+static CONFIG_WATCH_TASK: Once = Once::new();
 
 impl Handler {
     pub async fn ready(&self, ctx: Context, ready: Ready) {
@@ -34,19 +57,126 @@ impl Handler {
                 if ready.guilds.len() == 1 { "" } else { "s" }
             );
 
-            #[cfg(debug_assertions)]
-            ctx.set_presence(
-                Some(ActivityData::playing("DEBUG BUILD")),
-                OnlineStatus::DoNotDisturb,
-            );
+            let event = match DATABASE.get() {
+                Some(db) => active_event(db).await.unwrap_or_else(|e| {
+                    warn!("Failed to fetch the active event: {}", e);
+                    None
+                }),
+                None => None,
+            };
+            set_presence(&ctx, event.as_ref());
 
-            #[cfg(not(debug_assertions))]
-            ctx.set_presence(
-                Some(ActivityData::playing("/help to get started")),
-                OnlineStatus::Online,
-            );
+            SHARDS.lock().unwrap().insert(shard.id, ctx.clone());
+            SHARD_TOTAL.store(shard.total, Ordering::Relaxed);
+
+            PRESENCE_TASK.call_once(|| {
+                tokio::spawn(refresh_presence());
+            });
+            CONFIG_WATCH_TASK.call_once(|| {
+                tokio::spawn(config::watch(ctx.http.clone()));
+            });
+
+            if let Some(db) = DATABASE.get() {
+                LOTTERY_TASK.call_once(|| {
+                    tokio::spawn(run_draws(db.clone(), ctx.http.clone()));
+                });
+                HISTORY_TASK.call_once(|| {
+                    tokio::spawn(run_snapshots(db.clone()));
+                });
+                PROFILE_SYNC_TASK.call_once(|| {
+                    tokio::spawn(run_profile_sync(db.clone()));
+                });
+            }
         }
 
+        observe_event("ready", instant.elapsed());
         info!("Event ready | Time: {:?}", instant.elapsed());
     }
 }
+
+fn set_presence(ctx: &Context, event: Option<&Event>) {
+    if let Some(event) = event {
+        ctx.set_presence(
+            Some(ActivityData::playing(format!(
+                "🎉 {} | /play",
+                event.summary(DEFAULT_LOCALE)
+            ))),
+            OnlineStatus::Online,
+        );
+        return;
+    }
+
+    // The configured text can change with a config reload
+    let presence = config::dynamic().presence;
+
+    #[cfg(debug_assertions)]
+    ctx.set_presence(
+        Some(ActivityData::playing(
+            presence.unwrap_or_else(|| String::from("DEBUG BUILD")),
+        )),
+        OnlineStatus::DoNotDisturb,
+    );
+
+    #[cfg(not(debug_assertions))]
+    ctx.set_presence(
+        Some(ActivityData::playing(
+            presence.unwrap_or_else(|| String::from("/help to get started")),
+        )),
+        OnlineStatus::Online,
+    );
+}
+
+/// How many shards reported ready, out of how many the bot runs with (0 until the first one did).
+pub fn ready_shards() -> (usize, u32) {
+    (
+        SHARDS.lock().unwrap().len(),
+        SHARD_TOTAL.load(Ordering::Relaxed),
+    )
+}
+
+/// Sets the presence of every shard again, used when the configured text changes.
+pub async fn update_presence() {
+    let event = match DATABASE.get() {
+        Some(db) => active_event(db).await.unwrap_or_else(|e| {
+            warn!("Failed to fetch the active event: {}", e);
+            None
+        }),
+        None => None,
+    };
+
+    for ctx in SHARDS.lock().unwrap().values() {
+        set_presence(ctx, event.as_ref());
+    }
+}
+
+/// Keeps the presence of every shard in sync with the running bonus event.
+async fn refresh_presence() {
+    let mut current: Option<String> = None;
+
+    loop {
+        tokio::time::sleep(PRESENCE_REFRESH).await;
+
+        let Some(db) = DATABASE.get() else {
+            continue;
+        };
+
+        let event = match active_event(db).await {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("Failed to fetch the active event: {}", e);
+                continue;
+            }
+        };
+
+        // Only touch the presence when the event changes
+        let summary = event.as_ref().map(|event| event.summary(DEFAULT_LOCALE));
+        if summary == current {
+            continue;
+        }
+        current = summary;
+
+        for ctx in SHARDS.lock().unwrap().values() {
+            set_presence(ctx, event.as_ref());
+        }
+    }
+}
@@ -0,0 +1,116 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::DATABASE;
+use crate::handler::ready_shards;
+use crate::http::State;
+use mongodb::bson::doc;
+use serenity::all::{ShardId, ShardManager};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+// Discord asks for a heartbeat about every 41 seconds, a shard missing a few of them is stuck
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(150);
+// How often the shards are checked for new heartbeats
+const HEARTBEAT_CHECK: Duration = Duration::from_secs(5);
+// A readiness probe shouldn't hang on an unreachable database
+const PING_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Last latency measured for each shard and when it last changed.
+// Serenity doesn't tell when a heartbeat is acknowledged, but every acknowledgement measures a new latency.
+static HEARTBEATS: Mutex<BTreeMap<ShardId, (Option<Duration>, Instant)>> =
+    Mutex::new(BTreeMap::new());
+
+/// Result of a check, with a short explanation for whoever reads the probe output.
+pub struct Status {
+    pub ok: bool,
+    pub details: Vec<String>,
+}
+
+/// Keeps track of the heartbeats of every shard, for the liveness check.
+pub async fn watch_heartbeats(shard_manager: Arc<ShardManager>) {
+    loop {
+        tokio::time::sleep(HEARTBEAT_CHECK).await;
+
+        let runners = shard_manager.runners.lock().await;
+        let mut heartbeats = HEARTBEATS.lock().unwrap();
+        // Shards that went away aren't expected to beat anymore
+        heartbeats.retain(|id, _| runners.contains_key(id));
+        for (id, runner) in runners.iter() {
+            // A shard seen for the first time gets a full timeout to connect
+            let (latency, last) = heartbeats
+                .entry(*id)
+                .or_insert((runner.latency, Instant::now()));
+            if runner.latency != *latency {
+                *latency = runner.latency;
+                *last = Instant::now();
+            }
+        }
+    }
+}
+
+/// Alive as long as no shard went without a heartbeat for too long.
+pub fn liveness() -> Status {
+    let mut details = Vec::new();
+    for (id, (_, last)) in HEARTBEATS.lock().unwrap().iter() {
+        let elapsed = last.elapsed();
+        if elapsed > HEARTBEAT_TIMEOUT {
+            details.push(format!(
+                "shard {}: no heartbeat for {}s",
+                id,
+                elapsed.as_secs()
+            ));
+        }
+    }
+
+    Status {
+        ok: details.is_empty(),
+        details,
+    }
+}
+
+/// Ready once every shard reported ready and the database answers, until the bot shuts down.
+pub async fn readiness(state: &State) -> Status {
+    let mut details = Vec::new();
+
+    if state.sessions.is_closing() {
+        details.push(String::from("shutting down"));
+    }
+
+    let (ready, total) = ready_shards();
+    if total == 0 || ready < total as usize {
+        details.push(format!("shards: {}/{} ready", ready, total));
+    }
+
+    match DATABASE.get() {
+        Some(db) => {
+            match tokio::time::timeout(PING_TIMEOUT, db.run_command(doc! {"ping": 1})).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => details.push(format!("database: {}", e)),
+                Err(_) => details.push(String::from("database: ping timed out")),
+            }
+        }
+        None => details.push(String::from("database: not connected")),
+    }
+
+    Status {
+        ok: details.is_empty(),
+        details,
+    }
+}
@@ -0,0 +1,133 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::health::{self, Status};
+use crate::metrics;
+use crate::shutdown::Sessions;
+use serenity::all::{Cache, ShardManager};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, warn};
+
+// Scrapers send small requests, anything slower or bigger than this is dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_REQUEST_SIZE: usize = 4096;
+
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+const TEXT_CONTENT_TYPE: &str = "text/plain; charset=utf-8";
+
+/// What the HTTP endpoints report on, taken from the client once it's built.
+pub struct State {
+    pub cache: Arc<Cache>,
+    pub shard_manager: Arc<ShardManager>,
+    pub sessions: Arc<Sessions>,
+}
+
+struct Response {
+    status: &'static str,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn new(status: &'static str, content_type: &'static str, body: impl Into<String>) -> Self {
+        Response {
+            status,
+            content_type,
+            body: body.into(),
+        }
+    }
+
+    fn text(status: &'static str, body: impl Into<String>) -> Self {
+        Response::new(status, TEXT_CONTENT_TYPE, body)
+    }
+
+    // Probes only look at the status code, the body says what's wrong
+    fn probe(status: Status) -> Self {
+        if status.ok {
+            Response::text("200 OK", "ok\n")
+        } else {
+            Response::text(
+                "503 Service Unavailable",
+                format!("{}\n", status.details.join("\n")),
+            )
+        }
+    }
+}
+
+/// Serves the HTTP endpoints until the process exits.
+/// A minimal HTTP/1.1 server is enough for scrapers, and avoids pulling in a web framework.
+pub async fn serve(listener: TcpListener, state: Arc<State>) {
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept an HTTP connection: {}", e);
+                continue;
+            }
+        };
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle(stream, &state).await {
+                debug!("HTTP connection failed: {}", e);
+            }
+        });
+    }
+}
+
+async fn handle(mut stream: TcpStream, state: &State) -> io::Result<()> {
+    let mut buffer = vec![0; MAX_REQUEST_SIZE];
+    let read = tokio::time::timeout(REQUEST_TIMEOUT, stream.read(&mut buffer))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))??;
+
+    // Only the request line matters, headers and bodies are ignored
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let path = request_line.next().unwrap_or_default();
+    let path = path.split('?').next().unwrap_or_default();
+
+    let response = match (method, path) {
+        ("GET", "/metrics") => {
+            Response::new("200 OK", METRICS_CONTENT_TYPE, metrics::render(state).await)
+        }
+        ("GET", "/healthz") => Response::probe(health::liveness()),
+        ("GET", "/readyz") => Response::probe(health::readiness(state).await),
+        ("GET", _) => Response::text("404 Not Found", "Not Found\n"),
+        _ => Response::text("405 Method Not Allowed", "Method Not Allowed\n"),
+    };
+
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.content_type,
+        response.body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(response.body.as_bytes()).await?;
+    stream.shutdown().await
+}
@@ -0,0 +1,247 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::settings::Settings;
+use crate::commands::{Context, Data, Error};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+
+// Used when the user's locale has no catalog, or when a string is missing from it
+pub const DEFAULT_LOCALE: &str = "en";
+
+// Catalogs are embedded in the binary, the key is the Discord locale they apply to.
+// Discord locales are either a language ("fr") or a language and a region ("en-US").
+static CATALOGS: LazyLock<HashMap<&'static str, Catalog>> = LazyLock::new(|| {
+    [
+        ("en", include_str!("../locales/en.ron")),
+        ("fr", include_str!("../locales/fr.ron")),
+    ]
+    .into_iter()
+    .map(|(locale, catalog)| {
+        let catalog = ron::from_str(catalog)
+            .unwrap_or_else(|e| panic!("locales/{}.ron is invalid: {}", locale, e));
+        (locale, catalog)
+    })
+    .collect()
+});
+
+#[derive(Debug, Default, Deserialize)]
+struct Catalog {
+    // Display name of the language, shown in /settings
+    name: String,
+    #[serde(default)]
+    commands: HashMap<String, CommandTranslation>,
+    strings: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct CommandTranslation {
+    name: Option<String>,
+    description: Option<String>,
+    #[serde(default)]
+    parameters: HashMap<String, ParameterTranslation>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ParameterTranslation {
+    name: Option<String>,
+    description: Option<String>,
+    // Choice labels, keyed by their English label
+    #[serde(default)]
+    choices: HashMap<String, String>,
+}
+
+/// Translates a string, `{name}` placeholders are replaced by the matching argument.
+/// Falls back to the default locale, then to the key itself if the string doesn't exist.
+pub fn translate(locale: &str, key: &str, args: &[(&str, String)]) -> String {
+    let template = [locale, DEFAULT_LOCALE]
+        .iter()
+        .find_map(|locale| CATALOGS.get(locale)?.strings.get(key))
+        .map_or(key, String::as_str);
+
+    substitute(template, args)
+}
+
+// Single pass over the template, a value that contains a placeholder (e.g. a nickname) is never replaced in turn
+fn substitute(template: &str, args: &[(&str, String)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        text.push_str(&rest[..open]);
+        rest = &rest[open..];
+
+        let value = rest.find('}').and_then(|close| {
+            let (_, value) = args.iter().find(|(name, _)| *name == &rest[1..close])?;
+            Some((value, close))
+        });
+        match value {
+            Some((value, close)) => {
+                text.push_str(value);
+                rest = &rest[close + 1..];
+            }
+            // Not a known placeholder, the brace is kept as is
+            None => {
+                text.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+/// `tr!(locale, "key", name = value)` is a shorthand for `translate`.
+macro_rules! tr {
+    ($locale:expr, $key:expr) => {
+        $crate::i18n::translate($locale, $key, &[])
+    };
+    ($locale:expr, $key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::translate($locale, $key, &[$((stringify!($name), $value.to_string())),+])
+    };
+}
+pub(crate) use tr;
+
+/// Supported locales and their display name
+pub fn locales() -> Vec<(&'static str, &'static str)> {
+    let mut locales: Vec<_> = CATALOGS
+        .iter()
+        .map(|(locale, catalog)| (*locale, catalog.name.as_str()))
+        .collect();
+    locales.sort();
+    locales
+}
+
+/// Finds the catalog for a Discord locale, "en-US" uses "en" if there's no "en-US" catalog.
+pub fn resolve(locale: &str) -> Option<&'static str> {
+    let language = locale.split('-').next().unwrap_or(locale);
+    [locale, language]
+        .into_iter()
+        .find_map(|locale| CATALOGS.get_key_value(locale).map(|(key, _)| *key))
+}
+
+/// Locale to answer the author of the command in.
+/// The locale chosen in /settings is used first, then the one of their Discord client.
+pub async fn locale(ctx: Context<'_>) -> Result<&'static str, Error> {
+    Ok(locale_for(ctx, &Settings::load(ctx).await?))
+}
+
+/// Same as `locale`, for commands that already loaded the settings of the author.
+pub fn locale_for(ctx: Context<'_>, settings: &Settings) -> &'static str {
+    settings
+        .locale
+        .as_deref()
+        .and_then(resolve)
+        .or_else(|| ctx.locale().and_then(resolve))
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Fills the name and description localizations of the commands from the catalogs,
+/// so Discord shows them in the language of the client.
+pub fn localize_commands(commands: &mut [poise::Command<Data, Error>]) {
+    for command in commands {
+        localize_command(command, "");
+    }
+}
+
+fn localize_command(command: &mut poise::Command<Data, Error>, parent: &str) {
+    // Subcommands are identified by their full name, like "settings language"
+    let key = format!("{}{}", parent, command.name);
+
+    for (locale, catalog) in CATALOGS.iter() {
+        // Commands are written in the default locale, which isn't a valid Discord locale on its own
+        if *locale == DEFAULT_LOCALE {
+            continue;
+        }
+        let Some(translation) = catalog.commands.get(&key) else {
+            continue;
+        };
+
+        if let Some(name) = &translation.name {
+            command
+                .name_localizations
+                .insert(locale.to_string(), name.clone());
+        }
+        if let Some(description) = &translation.description {
+            command
+                .description_localizations
+                .insert(locale.to_string(), description.clone());
+        }
+
+        for parameter in &mut command.parameters {
+            let Some(translation) = translation.parameters.get(&parameter.name) else {
+                continue;
+            };
+            if let Some(name) = &translation.name {
+                parameter
+                    .name_localizations
+                    .insert(locale.to_string(), name.clone());
+            }
+            if let Some(description) = &translation.description {
+                parameter
+                    .description_localizations
+                    .insert(locale.to_string(), description.clone());
+            }
+            for choice in &mut parameter.choices {
+                if let Some(name) = translation.choices.get(&choice.name) {
+                    choice
+                        .localizations
+                        .insert(locale.to_string(), name.clone());
+                }
+            }
+        }
+    }
+
+    let parent = format!("{} ", key);
+    for subcommand in &mut command.subcommands {
+        localize_command(subcommand, &parent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::substitute;
+
+    fn check(template: &str, args: &[(&str, &str)], expected: &str) {
+        let args: Vec<(&str, String)> = args
+            .iter()
+            .map(|(name, value)| (*name, value.to_string()))
+            .collect();
+        assert_eq!(substitute(template, &args), expected, "{}", template);
+    }
+
+    #[test]
+    fn substitute_placeholders() {
+        check("no placeholder", &[], "no placeholder");
+        check("{a} and {b}", &[("a", "1"), ("b", "2")], "1 and 2");
+        check("{a}{a}", &[("a", "x")], "xx");
+        check("{missing} {a}", &[("a", "x")], "{missing} x");
+        check("{ {a} }", &[("a", "x")], "{ x }");
+        check("unclosed {a", &[("a", "x")], "unclosed {a");
+    }
+
+    #[test]
+    fn substituted_values_are_not_scanned_again() {
+        check(
+            "{name} bet {wager}",
+            &[("name", "{wager}"), ("wager", "5")],
+            "{wager} bet 5",
+        );
+        check("{a}{b}", &[("a", "{"), ("b", "b}")], "{b}");
+    }
+}
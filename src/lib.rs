@@ -17,32 +17,149 @@
  */
 
 mod commands;
+pub mod config;
+mod error;
+mod format;
 mod handler;
+mod health;
+mod http;
+mod i18n;
+mod metrics;
+mod shutdown;
 
 use crate::commands::Data;
+use crate::config::{Config, StaticConfig};
+use crate::shutdown::Sessions;
 use mongodb::Database;
-use mongodb::error::Error;
+use mongodb::bson::doc;
+use mongodb::error::ErrorKind;
+use mongodb::event::EventHandler;
 use mongodb::options::{ClientOptions, ServerApi, ServerApiVersion};
-use ron::ser::PrettyConfig;
-use serde::{Deserialize, Serialize};
 use serenity::prelude::*;
-use std::fs;
-use std::time::Instant;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::TcpListener;
 use tokio::sync::OnceCell;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 pub struct Handler;
 
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
-pub struct Config {
-    token: String,                   // Bot token
-    pub log_channel_id: Option<u64>, // (optional) Channel ID for logging
-    mongodb_uri: String,
+// Only the part of the config that needs a restart, the rest is read through config::dynamic()
+pub static CONFIG: OnceCell<StaticConfig> = OnceCell::const_new();
+// Shared with the event handlers, which don't have access to the framework's Data
+pub static DATABASE: OnceCell<Database> = OnceCell::const_new();
+
+// How many times the initial MongoDB connection is attempted before giving up.
+// The delay between attempts doubles each time, starting at DB_RETRY_DELAY.
+const DB_CONNECT_ATTEMPTS: u32 = 5;
+const DB_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// Everything that can stop the bot from starting.
+#[derive(Debug)]
+pub enum StartupError {
+    /// The config file exists but couldn't be read
+    ConfigRead(PathBuf, io::Error),
+    /// The config file isn't valid RON or is missing fields
+    ConfigParse(ron::error::SpannedError),
+    /// No config file was found, a default one was written and needs to be filled in
+    ConfigCreated,
+    /// No config file was found and the default one couldn't be written either
+    ConfigWrite(io::Error),
+    /// The config was read but some values are wrong, every problem is listed
+    ConfigInvalid(Vec<String>),
+    Database(mongodb::error::Error),
+    Discord(Box<serenity::Error>),
+    /// The log channel can't be fetched with the bot's token, only known after login
+    LogChannel(u64, String),
+    /// The HTTP server couldn't listen on the configured address
+    Http(String, io::Error),
 }
 
-pub static CONFIG: OnceCell<Config> = OnceCell::const_new();
+impl StartupError {
+    /// The process exit code for this error, so scripts and orchestrators can tell failures apart.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            StartupError::ConfigRead(..)
+            | StartupError::ConfigCreated
+            | StartupError::ConfigWrite(_) => 3,
+            StartupError::ConfigParse(_) | StartupError::ConfigInvalid(_) => 4,
+            StartupError::Database(_) => 5,
+            StartupError::Discord(_) => 6,
+            StartupError::LogChannel(..) => 7,
+            StartupError::Http(..) => 8,
+        }
+    }
+}
 
-pub async fn run() -> Result<Client, serenity::Error> {
+impl fmt::Display for StartupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartupError::ConfigRead(path, e) => {
+                write!(f, "Failed to read {}: {}", path.display(), e)
+            }
+            StartupError::ConfigParse(e) => write!(f, "Invalid config file: {}", e),
+            StartupError::ConfigCreated => write!(
+                f,
+                "Config file not found. A default config.ron was created, fill it in and restart"
+            ),
+            StartupError::ConfigWrite(e) => {
+                write!(
+                    f,
+                    "Config file not found and a default one couldn't be created: {}",
+                    e
+                )
+            }
+            StartupError::ConfigInvalid(problems) => {
+                write!(
+                    f,
+                    "Invalid configuration, {} problem(s) found:",
+                    problems.len()
+                )?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+            StartupError::Database(e) => write!(f, "Failed to connect to MongoDB: {}", e),
+            StartupError::Discord(e) => write!(f, "Discord client error: {}", e),
+            StartupError::LogChannel(id, e) => write!(
+                f,
+                "log_channel_id {} is not reachable: {}. Check the ID and the bot's access to it",
+                id, e
+            ),
+            StartupError::Http(address, e) => {
+                write!(f, "Failed to listen on {}: {}", address, e)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StartupError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StartupError::ConfigRead(_, e)
+            | StartupError::ConfigWrite(e)
+            | StartupError::Http(_, e) => Some(e),
+            StartupError::ConfigParse(e) => Some(e),
+            StartupError::ConfigCreated
+            | StartupError::ConfigInvalid(_)
+            | StartupError::LogChannel(..) => None,
+            StartupError::Database(e) => Some(e),
+            StartupError::Discord(e) => Some(e),
+        }
+    }
+}
+
+impl From<serenity::Error> for StartupError {
+    fn from(e: serenity::Error) -> Self {
+        StartupError::Discord(Box::new(e))
+    }
+}
+
+pub async fn run(config_path: Option<PathBuf>) -> Result<Client, StartupError> {
     info!("Starting Client");
 
     let intents = GatewayIntents::GUILD_MESSAGES
@@ -50,37 +167,69 @@ pub async fn run() -> Result<Client, serenity::Error> {
         | GatewayIntents::GUILDS
         | GatewayIntents::GUILD_MEMBERS;
 
-    let _ = CONFIG.get_or_init(|| async { get_config() }).await;
+    let _ = CONFIG.set(Config::init(config_path)?);
 
     let token = &CONFIG.get().unwrap().token;
 
+    let uri = &CONFIG.get().unwrap().mongodb_uri;
+    let mongo_client = connect_mongo(uri).await.map_err(StartupError::Database)?;
+    commands::profile::prepare_name_search(&mongo_client)
+        .await
+        .map_err(StartupError::Database)?;
+    commands::lottery::create_indexes(&mongo_client)
+        .await
+        .map_err(StartupError::Database)?;
+    let _ = DATABASE.set(mongo_client);
+
+    let mut commands = vec![
+        commands::help::help(),
+        commands::play::play(),
+        commands::profile::profile(),
+        commands::profile::view_profile(),
+        commands::compare::compare(),
+        commands::compare::compare_scores(),
+        commands::leaderboard::leaderboard(),
+        commands::duel::duel(),
+        commands::give::give(),
+        commands::quests::quests(),
+        commands::event::event(),
+        commands::lottery::lottery(),
+        commands::history::history(),
+        commands::settings::settings(),
+        commands::account::account(),
+        commands::ping::ping(),
+        commands::sync::sync(),
+        commands::info::info(),
+        commands::about::about(),
+        commands::admin::admin(),
+    ];
+    i18n::localize_commands(&mut commands);
+
+    // Created here because the shutdown needs it too, not only the commands
+    let sessions = Sessions::new();
+    let framework_sessions = sessions.clone();
+
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![
-                commands::help::help(),
-                commands::play::play(),
-                commands::profile::profile(),
-                commands::leaderboard::leaderboard(),
-                commands::ping::ping(),
-                commands::sync::sync(),
-                commands::info::info(),
-                commands::about::about(),
-            ],
+            commands,
+            on_error: |error| Box::pin(error::on_error(error)),
+            pre_command: |ctx| Box::pin(metrics::command_started(ctx)),
+            post_command: |ctx| Box::pin(metrics::command_finished(ctx)),
             ..Default::default()
         })
         .setup(|ctx, _, framework| {
             Box::pin(async move {
+                if let Some(channel) = config::dynamic().log_channel_id {
+                    config::check_log_channel(ctx, channel).await?;
+                }
+
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
                 info!("Slash commands registered");
 
-                let uri = &CONFIG.get().unwrap().mongodb_uri;
-                let mongo_client = create_mongo_client(uri)
-                    .await
-                    .expect("Failed to connect to MongoDB");
-
                 Ok(Data {
-                    db: mongo_client,
+                    db: DATABASE.get().cloned().ok_or("Database not initialized")?,
                     uptime: Instant::now(),
+                    sessions: framework_sessions,
                 })
             })
         })
@@ -92,51 +241,83 @@ pub async fn run() -> Result<Client, serenity::Error> {
         .framework(framework)
         .await?;
 
+    if let Some(address) = &CONFIG.get().unwrap().http_address {
+        let listener = TcpListener::bind(address)
+            .await
+            .map_err(|e| StartupError::Http(address.clone(), e))?;
+        info!(
+            "Serving /metrics, /healthz and /readyz on http://{}",
+            address
+        );
+
+        let state = http::State {
+            cache: client.cache.clone(),
+            shard_manager: client.shard_manager.clone(),
+            sessions: sessions.clone(),
+        };
+        tokio::spawn(health::watch_heartbeats(client.shard_manager.clone()));
+        tokio::spawn(http::serve(listener, Arc::new(state)));
+    }
+
+    let shard_manager = client.shard_manager.clone();
+    let shutdown_sessions = sessions.clone();
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        shutdown::shutdown(shutdown_sessions, shard_manager).await;
+    });
+
     client.start_autosharded().await?;
 
+    // The shards only stop on their own when asked to
+    if sessions.is_closing() {
+        return Ok(client);
+    }
+
     error!(
         "This part of the code shouldn't be reached. If you see this, something went wrong. and the bot has probably crashed."
     );
     Ok(client)
 }
 
-async fn create_mongo_client(secret: &str) -> Result<Database, Error> {
+// Retries the connection with exponential backoff, the database might still be starting up
+// (e.g. when both are launched together by docker compose).
+async fn connect_mongo(uri: &str) -> Result<Database, mongodb::error::Error> {
+    let mut delay = DB_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        match create_mongo_client(uri).await {
+            Ok(db) => return Ok(db),
+            // A malformed URI won't fix itself, no point in retrying
+            Err(e) if matches!(*e.kind, ErrorKind::InvalidArgument { .. }) => return Err(e),
+            Err(e) if attempt < DB_CONNECT_ATTEMPTS => {
+                warn!(
+                    "MongoDB connection attempt {}/{} failed: {}. Retrying in {:?}",
+                    attempt, DB_CONNECT_ATTEMPTS, e, delay
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+async fn create_mongo_client(secret: &str) -> Result<Database, mongodb::error::Error> {
     let now = Instant::now();
     info!("Connecting to MongoDB");
     let mut client_options = ClientOptions::parse(secret).await?;
     let server_api = ServerApi::builder().version(ServerApiVersion::V1).build();
     client_options.server_api = Some(server_api);
+    client_options.command_event_handler =
+        Some(EventHandler::callback(metrics::observe_db_command));
 
     let client = mongodb::Client::with_options(client_options)?;
     let db = client.database("button_clicker");
 
+    // The client connects lazily, ping so an unreachable server is caught at startup
+    db.run_command(doc! {"ping": 1}).await?;
+
     info!("Connected to MongoDB | Time: {:?}", now.elapsed());
     Ok(db)
 }
-
-fn get_config() -> Config {
-    // The RON file is better in this case because it's one of the few serde impl that actually supports
-    // the u64 format without having to do parsing and conversion and stuff.
-    // + it's "native" to Rust's type of syntax.
-    match fs::read_to_string("config.ron") {
-        Ok(config) => {
-            let config = ron::from_str(&config).unwrap();
-            info!("Loaded config from file");
-            config
-        }
-        Err(_) => {
-            error!("Config file not found. Creating a default config file.");
-
-            // Create a default config
-            let config = Config::default();
-
-            // Write the default config to a file
-            let config = ron::ser::to_string_pretty(&config, PrettyConfig::default()).unwrap();
-
-            fs::write("config.ron", config).unwrap();
-
-            // Exit
-            std::process::exit(1);
-        }
-    }
-}
@@ -16,13 +16,29 @@
  *  as defined by the AGPLv3 license.
  */
 
+use button_clicker::config::{Command, Config};
 use button_clicker::run;
-use serenity::Error;
-use tracing::{Level, info};
+use tracing::{Level, error, info};
 use tracing_subscriber::FmtSubscriber;
 
 #[tokio::main]
-async fn main() -> Result<(), Error> {
+async fn main() {
+    let config_path = match Command::parse(std::env::args().skip(1)) {
+        Ok(Command::Run { config_path }) => config_path,
+        Ok(Command::PrintDefaultConfig) => {
+            println!("{}", Config::default_file());
+            return;
+        }
+        Ok(Command::Help) => {
+            println!("{}", Command::usage());
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, Command::usage());
+            std::process::exit(2);
+        }
+    };
+
     // Set up tracing
     #[cfg(debug_assertions)]
     let subscriber = FmtSubscriber::builder()
@@ -39,7 +55,8 @@ async fn main() -> Result<(), Error> {
     info!("Starting Main");
 
     // Run app
-    run().await?;
-
-    Ok(())
+    if let Err(e) = run(config_path).await {
+        error!("{}", e);
+        std::process::exit(e.exit_code());
+    }
 }
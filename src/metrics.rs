@@ -0,0 +1,218 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::commands::Context;
+use crate::http::State;
+use mongodb::event::command::CommandEvent;
+use prometheus::{
+    Encoder, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+// In seconds, from a quick database query up to a slow Discord request
+const LATENCY_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+/// Every metric exposed on /metrics, all prefixed with button_clicker_.
+pub struct Metrics {
+    registry: Registry,
+    pub commands: IntCounterVec,
+    // Long running commands like /play are measured until they end, clicks are measured separately
+    pub command_duration: HistogramVec,
+    pub clicks: IntCounter,
+    pub click_duration: Histogram,
+    pub event_duration: HistogramVec,
+    pub db_duration: HistogramVec,
+    pub db_failures: IntCounterVec,
+    // The gauges below are set on every scrape
+    active_sessions: IntGauge,
+    guilds: IntGauge,
+    shard_latency: GaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let registry = Registry::new_custom(Some(String::from("button_clicker")), None)
+            .expect("the prefix is valid");
+
+        let metrics = Metrics {
+            commands: IntCounterVec::new(
+                Opts::new("commands_total", "Commands executed"),
+                &["command"],
+            )
+            .expect("valid metric"),
+            command_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "command_duration_seconds",
+                    "Time taken by successful commands",
+                )
+                .buckets(LATENCY_BUCKETS.to_vec()),
+                &["command"],
+            )
+            .expect("valid metric"),
+            clicks: IntCounter::new("clicks_total", "Clicks processed in /play sessions")
+                .expect("valid metric"),
+            click_duration: Histogram::with_opts(
+                HistogramOpts::new("click_duration_seconds", "Time taken to process a click")
+                    .buckets(LATENCY_BUCKETS.to_vec()),
+            )
+            .expect("valid metric"),
+            event_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "event_duration_seconds",
+                    "Time taken by gateway event handlers",
+                )
+                .buckets(LATENCY_BUCKETS.to_vec()),
+                &["event"],
+            )
+            .expect("valid metric"),
+            db_duration: HistogramVec::new(
+                HistogramOpts::new(
+                    "db_operation_duration_seconds",
+                    "Time taken by MongoDB commands",
+                )
+                .buckets(LATENCY_BUCKETS.to_vec()),
+                &["operation"],
+            )
+            .expect("valid metric"),
+            db_failures: IntCounterVec::new(
+                Opts::new(
+                    "db_operation_failures_total",
+                    "MongoDB commands that failed",
+                ),
+                &["operation"],
+            )
+            .expect("valid metric"),
+            active_sessions: IntGauge::new("active_sessions", "Open /play sessions and duels")
+                .expect("valid metric"),
+            guilds: IntGauge::new("guilds", "Guilds the bot is in").expect("valid metric"),
+            shard_latency: GaugeVec::new(
+                Opts::new(
+                    "shard_latency_seconds",
+                    "Gateway heartbeat latency of each shard",
+                ),
+                &["shard"],
+            )
+            .expect("valid metric"),
+            registry,
+        };
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 10] = [
+            Box::new(metrics.commands.clone()),
+            Box::new(metrics.command_duration.clone()),
+            Box::new(metrics.clicks.clone()),
+            Box::new(metrics.click_duration.clone()),
+            Box::new(metrics.event_duration.clone()),
+            Box::new(metrics.db_duration.clone()),
+            Box::new(metrics.db_failures.clone()),
+            Box::new(metrics.active_sessions.clone()),
+            Box::new(metrics.guilds.clone()),
+            Box::new(metrics.shard_latency.clone()),
+        ];
+        for collector in collectors {
+            metrics
+                .registry
+                .register(collector)
+                .expect("metric names are unique");
+        }
+
+        metrics
+    }
+}
+
+/// Records a gateway event handler taking `elapsed`.
+pub fn observe_event(event: &str, elapsed: Duration) {
+    METRICS
+        .event_duration
+        .with_label_values(&[event])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Set as pre_command in the FrameworkOptions.
+pub async fn command_started(ctx: Context<'_>) {
+    METRICS
+        .commands
+        .with_label_values(&[&ctx.command().qualified_name])
+        .inc();
+    ctx.set_invocation_data(Instant::now()).await;
+}
+
+/// Set as post_command in the FrameworkOptions, only called when the command succeeded.
+pub async fn command_finished(ctx: Context<'_>) {
+    let Some(elapsed) = ctx
+        .invocation_data::<Instant>()
+        .await
+        .map(|started| started.elapsed())
+    else {
+        return;
+    };
+    METRICS
+        .command_duration
+        .with_label_values(&[&ctx.command().qualified_name])
+        .observe(elapsed.as_secs_f64());
+}
+
+/// Set as the command event handler of the MongoDB client, which times every command it sends.
+pub fn observe_db_command(event: CommandEvent) {
+    match event {
+        CommandEvent::Succeeded(event) => METRICS
+            .db_duration
+            .with_label_values(&[&event.command_name])
+            .observe(event.duration.as_secs_f64()),
+        CommandEvent::Failed(event) => {
+            METRICS
+                .db_duration
+                .with_label_values(&[&event.command_name])
+                .observe(event.duration.as_secs_f64());
+            METRICS
+                .db_failures
+                .with_label_values(&[&event.command_name])
+                .inc();
+        }
+        _ => {}
+    }
+}
+
+/// Renders every metric in the Prometheus text format.
+pub async fn render(state: &State) -> String {
+    METRICS.active_sessions.set(state.sessions.count() as i64);
+    METRICS.guilds.set(state.cache.guilds().len() as i64);
+
+    // Shards that went away (e.g. after a reshard) shouldn't keep reporting their last latency
+    METRICS.shard_latency.reset();
+    for (id, runner) in state.shard_manager.runners.lock().await.iter() {
+        if let Some(latency) = runner.latency {
+            METRICS
+                .shard_latency
+                .with_label_values(&[&id.to_string()])
+                .set(latency.as_secs_f64());
+        }
+    }
+
+    let mut buffer = Vec::new();
+    if let Err(e) = TextEncoder::new().encode(&METRICS.registry.gather(), &mut buffer) {
+        warn!("Failed to encode metrics: {}", e);
+    }
+    String::from_utf8(buffer).unwrap_or_default()
+}
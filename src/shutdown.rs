@@ -0,0 +1,141 @@
+/*
+ *  Copyright (C) 2025 Alex Olemans
+ *
+ *  This program is free software: you can redistribute it and/or modify
+ *  it under the terms of the GNU Affero General Public License as published by
+ *  the Free Software Foundation, version 3 of the License.
+ *
+ *  This program is distributed WITHOUT ANY WARRANTY; without even the implied warranty of
+ *  MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ *  GNU Affero General Public License for details.
+ *
+ *  You should have received a copy of the GNU Affero General Public License
+ *  along with this program. If not, see <https://www.gnu.org/licenses/agpl-3.0.html>.
+ *
+ *  This software may be subject to the AGPLv3 license if it is used as a service over a network,
+ *  as defined by the AGPLv3 license.
+ */
+
+use crate::DATABASE;
+use crate::handler::flush_profiles;
+use serenity::all::ShardManager;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+// How long open sessions get to end themselves before the shards are stopped anyway
+const SESSION_CLOSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Registry of the running /play sessions and duels, shared with the commands through Data.
+pub struct Sessions {
+    open: watch::Sender<usize>,
+    closing: watch::Sender<bool>,
+}
+
+/// Held by a session while it runs, the registry is updated when it's dropped.
+pub struct Session {
+    sessions: Arc<Sessions>,
+    closing: watch::Receiver<bool>,
+}
+
+impl Sessions {
+    pub fn new() -> Arc<Sessions> {
+        Arc::new(Sessions {
+            open: watch::Sender::new(0),
+            closing: watch::Sender::new(false),
+        })
+    }
+
+    /// Registers a new session, None once the bot is shutting down.
+    pub fn open(self: &Arc<Self>) -> Option<Session> {
+        // Counted first so a shutdown starting meanwhile waits for this session too
+        self.open.send_modify(|open| *open += 1);
+        let session = Session {
+            sessions: self.clone(),
+            closing: self.closing.subscribe(),
+        };
+        if self.is_closing() {
+            return None;
+        }
+        Some(session)
+    }
+
+    /// Number of sessions running in this process.
+    pub fn count(&self) -> usize {
+        *self.open.borrow()
+    }
+
+    pub fn is_closing(&self) -> bool {
+        *self.closing.borrow()
+    }
+
+    /// Tells every session to end and waits until they did, or the timeout elapsed.
+    async fn close_all(&self, timeout: Duration) {
+        self.closing.send_replace(true);
+
+        let mut open = self.open.subscribe();
+        if tokio::time::timeout(timeout, open.wait_for(|open| *open == 0))
+            .await
+            .is_err()
+        {
+            warn!("{} session(s) didn't end in time", self.count());
+        }
+    }
+}
+
+impl Session {
+    /// Resolves when the bot starts shutting down.
+    pub async fn closed(&mut self) {
+        // The sender lives in the registry which this session keeps alive, so this can't fail
+        let _ = self.closing.wait_for(|closing| *closing).await;
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        self.sessions.open.send_modify(|open| *open -= 1);
+    }
+}
+
+/// Resolves on Ctrl+C, or on SIGTERM which is what container runtimes send.
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Ends the sessions, writes what's still queued and disconnects every shard.
+pub async fn shutdown(sessions: Arc<Sessions>, shard_manager: Arc<ShardManager>) {
+    let time = Instant::now();
+    info!("Shutting down, ending {} session(s)", sessions.count());
+
+    sessions.close_all(SESSION_CLOSE_TIMEOUT).await;
+
+    if let Some(db) = DATABASE.get()
+        && let Err(e) = flush_profiles(db).await
+    {
+        warn!("Failed to sync profiles before shutting down: {}", e);
+    }
+
+    shard_manager.shutdown_all().await;
+
+    info!("Shut down | Time: {:?}", time.elapsed());
+}